@@ -1,3 +1,6 @@
 // HACK: need this build script so that env var OUT_DIR gets set:
 // https://doc.rust-lang.org/cargo/reference/environment-variables.html#environment-variables-cargo-sets-for-crates
-fn main() {}
+fn main() {
+    // `NEAR_SANDBOX_VERSION` is read with `option_env!` to override the default sandbox version
+    println!("cargo:rerun-if-env-changed=NEAR_SANDBOX_VERSION");
+}
@@ -9,11 +9,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
 
     let user: AccountId = "user.testnet".parse()?;
 
-    near_api::Tokens::account(user.clone())
-        .near_balance()
-        .fetch_from(&sandbox_network)
-        .await
-        .expect_err("User account should not exist");
+    assert!(
+        !sandbox.account_exists(&user.as_str().parse()?).await?,
+        "User account should not exist"
+    );
 
     sandbox
         .create_account(user.as_str().parse().unwrap())
@@ -9,12 +9,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
             "epoch_length": 100,
         })),
         additional_accounts: vec![
-            GenesisAccount {
-                account_id: "alice.near".parse().unwrap(),
-                public_key:"ed25519:AzBN9XwQDRuLvGvor2JnMitkRxBxn2TLY4yEM3othKUF".to_string(),
-                private_key: "ed25519:5byt6y8h1uuHwkr2ozfN5gt8xGiHujpcT5KyNhZpG62BrnU51sMQk5eTVNwWp7RRiMgKHp7W1jrByxLCr2apXNGB".to_string(),
-                balance: NearToken::from_near(1000),
-            },
+            GenesisAccount::new(
+                "alice.near".parse().unwrap(),
+                "ed25519:AzBN9XwQDRuLvGvor2JnMitkRxBxn2TLY4yEM3othKUF".to_string(),
+                "ed25519:5byt6y8h1uuHwkr2ozfN5gt8xGiHujpcT5KyNhZpG62BrnU51sMQk5eTVNwWp7RRiMgKHp7W1jrByxLCr2apXNGB".to_string(),
+                NearToken::from_near(1000),
+            ),
         ],
         rpc_port: Some(3030),
         ..Default::default()
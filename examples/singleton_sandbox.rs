@@ -25,7 +25,8 @@
 //! - Parallel-friendly - isolating tests with subaccount prevents test interference
 //!
 //! ## Cons
-//! - Higher memory usage - state accumulates as tests deploy contracts, produce blocks, and generate receipts (no cleanup between tests)
+//! - Higher memory usage - state accumulates as tests deploy contracts, produce blocks, and generate receipts
+//!   (no cleanup between tests unless `Sandbox::reset_state()` is called to wipe the chain back to genesis)
 //! - Shared state - tests are not fully isolated; one test's `fast_forward()` affects others
 //!
 //! ## Run This Example
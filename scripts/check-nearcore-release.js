@@ -6,12 +6,35 @@ const path = require('path');
 const NEARCORE_REPO = 'near/nearcore';
 const LIB_RS_PATH = 'src/lib.rs';
 
+const REQUEST_TIMEOUT_MS = 10_000;
+const REQUEST_RETRIES = 3;
+
 async function makeRequest(url) {
-    const response = await fetch(url);
-    if (!response.ok) {
-        throw new Error(`HTTP ${response.status}: ${response.statusText}`);
+    // Authenticated requests get 5000 req/hr instead of 60 req/hr shared by the whole runner IP
+    const headers = { 'User-Agent': 'near-sandbox-rs' };
+    if (process.env.GITHUB_TOKEN) {
+        headers['Authorization'] = `Bearer ${process.env.GITHUB_TOKEN}`;
+    }
+
+    for (let attempt = 1; ; attempt++) {
+        try {
+            const response = await fetch(url, {
+                headers,
+                signal: AbortSignal.timeout(REQUEST_TIMEOUT_MS),
+            });
+            if (!response.ok) {
+                throw new Error(`HTTP ${response.status}: ${response.statusText}`);
+            }
+            return await response.text();
+        } catch (error) {
+            if (attempt >= REQUEST_RETRIES) {
+                throw error;
+            }
+            const backoffMs = 1000 * 2 ** (attempt - 1);
+            console.warn(`Request to ${url} failed (${error.message}), retrying in ${backoffMs}ms...`);
+            await new Promise(resolve => setTimeout(resolve, backoffMs));
+        }
     }
-    return response.text();
 }
 
 async function getLatestNearCoreVersion() {
@@ -11,27 +11,52 @@
 // replicate all the structs from nearcore side; which can be a huge maintenance
 // churn if we were to.
 
+use std::collections::{BTreeSet, HashMap};
 use std::fs::File;
+use std::future::Future;
 use std::io::{BufReader, Write};
-use std::path::Path;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
 use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
 use near_account_id::{AccountId, AccountIdRef};
 use near_token::NearToken;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use sha2::{Digest, Sha256};
 
+use crate::Sandbox;
 use crate::error_kind::SandboxConfigError;
+use crate::sandbox::patch::StateRecord;
 
-/// Users won't get access to this account without patching
+/// Account the new accounts are copied from by [`crate::ConnectedSandbox::create_account`].
 ///
-/// We will use this account for copying account during creation of a new account
-pub(crate) const DEFAULT_ACCOUNT_FOR_CLONING: &AccountIdRef = AccountIdRef::new_or_panic("clone");
+/// It's always added to the genesis records with the default balance and the
+/// [`DEFAULT_GENESIS_ACCOUNT_PUBLIC_KEY`] full access key, so it can be signed for with
+/// [`DEFAULT_GENESIS_ACCOUNT_PRIVATE_KEY`] like the default genesis account. If the records are replaced with
+/// [`SandboxConfig::additional_genesis`], use [`AccountCreation::from_template`](crate::sandbox::account::AccountCreation::from_template)
+/// to copy another existing account instead.
+pub const DEFAULT_ACCOUNT_FOR_CLONING: &AccountIdRef = AccountIdRef::new_or_panic("clone");
+/// Seed the node and validator keys are derived from with [`SandboxConfig::deterministic`]
+pub(crate) const DETERMINISTIC_TEST_SEED: &str = "near-sandbox";
+/// Genesis time used with [`SandboxConfig::deterministic`]
+pub const DETERMINISTIC_GENESIS_TIME: &str = "2020-01-01T00:00:00Z";
 pub const DEFAULT_GENESIS_ACCOUNT: &AccountIdRef = AccountIdRef::new_or_panic("sandbox");
 pub const DEFAULT_GENESIS_ACCOUNT_PRIVATE_KEY: &str = "ed25519:3tgdk2wPraJzT4nsTuf86UX41xgPNk3MHnq8epARMdBNs29AFEztAuaQ7iHddDfXG9F2RzV1XNQYgJyAyoW51UBB";
 pub const DEFAULT_GENESIS_ACCOUNT_PUBLIC_KEY: &str =
     "ed25519:5BGSaf6YjVm7565VzWQHNxoyEjwr3jUpRJSGjREvU9dB";
 pub const DEFAULT_GENESIS_ACCOUNT_BALANCE: NearToken = NearToken::from_near(10_000);
+/// Timeout of the RPC requests made by the sandbox helpers, see [`SandboxConfig::rpc_timeout`]
+pub const DEFAULT_RPC_TIMEOUT: Duration = Duration::from_secs(30);
+/// Retries of the RPC requests failed with a transient error, see [`SandboxConfig::rpc_retries`]
+pub const DEFAULT_RPC_RETRIES: usize = 3;
+/// Size of the captured logs kept in memory, see [`SandboxConfig::log_buffer_bytes`]
+pub const DEFAULT_LOG_BUFFER_BYTES: usize = 4 * 1024 * 1024;
 
 /// Generates a pseudo-random AccountId for testing and development
 ///
@@ -94,17 +119,51 @@ pub struct GenesisAccount {
     pub public_key: String,
     pub private_key: String,
     pub balance: NearToken,
+    /// Contract deployed to the account at genesis, set with [`Self::with_code`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) code: Option<Vec<u8>>,
+    /// Contract storage as `(key, value)` pairs written to the genesis, added with [`Self::with_storage_entry`]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) storage: Vec<(Vec<u8>, Vec<u8>)>,
 }
 
 impl GenesisAccount {
-    pub fn default_with_name(name: AccountId) -> Self {
+    pub fn new(
+        account_id: AccountId,
+        public_key: String,
+        private_key: String,
+        balance: NearToken,
+    ) -> Self {
         Self {
-            account_id: name,
-            public_key: DEFAULT_GENESIS_ACCOUNT_PUBLIC_KEY.to_string(),
-            private_key: DEFAULT_GENESIS_ACCOUNT_PRIVATE_KEY.to_string(),
-            balance: DEFAULT_GENESIS_ACCOUNT_BALANCE,
+            account_id,
+            public_key,
+            private_key,
+            balance,
+            code: None,
+            storage: vec![],
         }
     }
+
+    /// Deploy the contract to the account at genesis, so it's a part of the initial chain state
+    pub fn with_code(mut self, code: Vec<u8>) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    /// Add a contract storage entry to the genesis
+    pub fn with_storage_entry(mut self, key: Vec<u8>, value: Vec<u8>) -> Self {
+        self.storage.push((key, value));
+        self
+    }
+
+    pub fn default_with_name(name: AccountId) -> Self {
+        Self::new(
+            name,
+            DEFAULT_GENESIS_ACCOUNT_PUBLIC_KEY.to_string(),
+            DEFAULT_GENESIS_ACCOUNT_PRIVATE_KEY.to_string(),
+            DEFAULT_GENESIS_ACCOUNT_BALANCE,
+        )
+    }
 }
 
 #[cfg(feature = "generate")]
@@ -121,6 +180,8 @@ impl GenesisAccount {
             public_key,
             private_key,
             balance: DEFAULT_GENESIS_ACCOUNT_BALANCE,
+            code: None,
+            storage: vec![],
         }
     }
 
@@ -132,6 +193,8 @@ impl GenesisAccount {
             public_key,
             private_key,
             balance: DEFAULT_GENESIS_ACCOUNT_BALANCE,
+            code: None,
+            storage: vec![],
         }
     }
 
@@ -143,6 +206,8 @@ impl GenesisAccount {
             public_key,
             private_key,
             balance,
+            code: None,
+            storage: vec![],
         }
     }
 
@@ -154,10 +219,76 @@ impl GenesisAccount {
             public_key,
             private_key,
             balance,
+            code: None,
+            storage: vec![],
         }
     }
 }
 
+impl GenesisAccount {
+    /// Base58 encoded sha256 of the code, or the hash of no code
+    pub(crate) fn code_hash(&self) -> String {
+        match &self.code {
+            Some(code) => bs58::encode(Sha256::digest(code)).into_string(),
+            None => "11111111111111111111111111111111".to_owned(),
+        }
+    }
+
+    /// Storage usage of the account with a single full access key, as computed by the runtime
+    pub(crate) fn storage_usage(&self) -> u64 {
+        // NOTE: Account record and the full access key take 182 bytes
+        const ACCOUNT_WITH_KEY_STORAGE_USAGE: u64 = 182;
+        // Per-record overhead of data entries (`storage_num_extra_bytes_record` in the runtime config)
+        const DATA_RECORD_EXTRA_BYTES: u64 = 40;
+
+        let code_len = self.code.as_ref().map_or(0, Vec::len) as u64;
+        let storage_len: u64 = self
+            .storage
+            .iter()
+            .map(|(key, value)| DATA_RECORD_EXTRA_BYTES + (key.len() + value.len()) as u64)
+            .sum();
+
+        ACCOUNT_WITH_KEY_STORAGE_USAGE + code_len + storage_len
+    }
+
+    /// State records the account is written to the genesis with
+    pub(crate) fn state_records(&self) -> Vec<StateRecord> {
+        let mut records = vec![StateRecord::Account {
+            account_id: self.account_id.clone(),
+            account: serde_json::json!({
+                "amount": self.balance,
+                "locked": "0",
+                "code_hash": self.code_hash(),
+                "storage_usage": self.storage_usage(),
+            }),
+        }];
+
+        if let Some(code) = &self.code {
+            records.push(StateRecord::Contract {
+                account_id: self.account_id.clone(),
+                code_base64: BASE64_STANDARD.encode(code),
+            });
+        }
+
+        records.extend(self.storage.iter().map(|(key, value)| StateRecord::Data {
+            account_id: self.account_id.clone(),
+            data_key_base64: BASE64_STANDARD.encode(key),
+            value_base64: BASE64_STANDARD.encode(value),
+        }));
+
+        records.push(StateRecord::AccessKey {
+            account_id: self.account_id.clone(),
+            public_key_base64: self.public_key.clone(),
+            access_key: serde_json::json!({
+                "nonce": 0,
+                "permission": "FullAccess",
+            }),
+        });
+
+        records
+    }
+}
+
 impl Default for GenesisAccount {
     fn default() -> Self {
         Self {
@@ -165,10 +296,21 @@ impl Default for GenesisAccount {
             public_key: DEFAULT_GENESIS_ACCOUNT_PUBLIC_KEY.to_string(),
             private_key: DEFAULT_GENESIS_ACCOUNT_PRIVATE_KEY.to_string(),
             balance: DEFAULT_GENESIS_ACCOUNT_BALANCE,
+            code: None,
+            storage: vec![],
         }
     }
 }
 
+/// Complete `config.json` of neard, see [`SandboxConfig::config_override`]
+#[derive(Debug, Clone)]
+pub enum ConfigOverride {
+    /// Path of the config file to use
+    File(PathBuf),
+    /// Contents of the config file
+    Json(Value),
+}
+
 /// Configuration for the sandbox
 #[derive(Debug, Clone, Default)]
 pub struct SandboxConfig {
@@ -176,35 +318,401 @@ pub struct SandboxConfig {
     pub max_payload_size: Option<usize>,
     /// Maximum number of open files
     pub max_open_files: Option<usize>,
+    /// RocksDB and trie cache settings
+    pub store: StoreConfig,
+    /// Maximum gas that can be burnt by a view call. Maps to `max_gas_burnt_view` in `config.json`
+    pub max_gas_burnt_view: Option<u64>,
+    /// Track all the shards instead of only the ones assigned to the sandbox validator, so view
+    /// queries work for accounts on any shard of a multi-shard genesis.
+    ///
+    /// Maps to `tracked_shards_config` in `config.json` (`AllShards` or `NoShards`). If unset, the
+    /// value generated by `neard init` is kept, so older neard versions without the key still start.
+    pub track_all_shards: Option<bool>,
+    /// Maximum gas that can be spent in a single chunk. Maps to `gas_limit` in `genesis.json`
+    ///
+    /// NOTE: Per-transaction limits (e.g. `max_total_prepaid_gas`, `max_transaction_size`) are
+    /// part of the protocol-versioned runtime config of nearcore and can't be overridden via genesis.
+    pub gas_limit: Option<u64>,
+    /// Number of blocks a transaction stays valid for. Maps to `transaction_validity_period` in `genesis.json`
+    pub transaction_validity_period: Option<u64>,
+    /// Inflation and reward settings of the genesis
+    pub economics: EconomicsConfig,
+    /// Validator seats of the genesis
+    pub consensus: ConsensusConfig,
+    /// Remove the sources of randomness that nearcore allows to control, so the same inputs produce the same state.
+    ///
+    /// Node and validator keys are derived from a fixed seed instead of being random, and `genesis_time`
+    /// is pinned to [`DETERMINISTIC_GENESIS_TIME`]. Block timestamps still come from the wall clock, so
+    /// block hashes and anything that depends on `block_timestamp` will differ between runs.
+    pub deterministic: bool,
+    /// Time of the genesis block. Maps to `genesis_time` in `genesis.json`, defaults to the time the sandbox is started.
+    ///
+    /// Useful with [`crate::ConnectedSandbox::fast_forward`] for tests of timestamp-based logic (e.g. vesting).
+    pub genesis_time: Option<SystemTime>,
     /// Additional JSON configuration to merge with the default config
+    ///
+    /// neard runs in the home dir of the sandbox, so relative paths in the config are resolved against it.
     pub additional_config: Option<Value>,
+    /// Complete `config.json` replacing the one generated by `neard init`, for users maintaining
+    /// a hand-tuned config.
+    ///
+    /// It's used as is: the settings of this struct that map to `config.json` (e.g. [`Self::additional_config`],
+    /// [`Self::store`] or [`Self::max_payload_size`]) are not applied. The ports are still the ones picked
+    /// by the sandbox, as they are passed to neard on the command line. Keep the
+    /// `rpc.limits_config.json_payload_max_size` in line with [`Self::max_payload_size`], which is used
+    /// to split large state patches.
+    pub config_override: Option<ConfigOverride>,
     /// Additional accounts to add to the genesis
     pub additional_accounts: Vec<GenesisAccount>,
     /// Additional JSON configuration to merge with the genesis
     pub additional_genesis: Option<Value>,
     /// Port that RPC will be bound to. Will be picked randomly if not set.
     pub rpc_port: Option<u16>,
+    /// Range to pick a free RPC port from. Ignored if [`Self::rpc_port`] is set.
+    pub rpc_port_range: Option<RangeInclusive<u16>>,
     /// Port that Network will be bound to. Will be picked randomly if not set.
     pub net_port: Option<u16>,
-    /// Number of retries to send port to sandbox instance. Will be set to 5 by default.
+    /// Total number of attempts to start the sandbox, each on freshly acquired ports, if it doesn't
+    /// become ready within the startup timeout. Defaults to `NEAR_SANDBOX_PORT_TRANSFER_RETRY` env var or 5.
     pub port_transfer_retries: Option<usize>,
+    /// Directory to create the sandbox home dir (including the RocksDB data) in.
+    /// Defaults to the system temp dir, which honors the `TMPDIR` env var.
+    ///
+    /// Useful to put the state on fast storage, or off a size-limited tmpfs on CI.
+    pub temp_dir_root: Option<PathBuf>,
+    /// Keep the home dir, including the RocksDB data, in memory by creating it on the `/dev/shm` tmpfs.
+    ///
+    /// neard has no in-memory store, so this is Linux-only: elsewhere, or if `/dev/shm` doesn't exist, the
+    /// sandbox falls back to the regular temp dir with a warning. Speeds up the startup and the state
+    /// operations, but the state takes RAM and is lost with the sandbox, which is the case for the
+    /// temp dir anyway. Ignored if [`Self::temp_dir_root`] is set.
+    ///
+    /// Docker limits `/dev/shm` to 64 MiB by default, which RocksDB quickly fills up, failing with
+    /// `ENOSPC` (no space left on device). Raise it with `--shm-size` when running in a container.
+    pub in_memory_store: bool,
+    /// Directory for port lock files. Defaults to `NEAR_SANDBOX_LOCK_DIR` env var or the system temp dir.
+    pub lock_dir: Option<PathBuf>,
+    /// Timeout applied to every RPC request made through the sandbox helpers. Defaults to [`DEFAULT_RPC_TIMEOUT`].
+    ///
+    /// The waits of [`crate::ConnectedSandbox::fast_forward`] and [`crate::ConnectedSandbox::produce_blocks`]
+    /// get this timeout plus an allowance per awaited block, so lowering it doesn't make the large jumps fail.
+    ///
+    /// Doesn't affect the startup, which is limited by the `NEAR_RPC_TIMEOUT_SECS` env var.
+    pub rpc_timeout: Option<Duration>,
+    /// Number of times an RPC request is retried, with an exponential backoff, after a transient error:
+    /// a connection failure, a 5xx response or a `TIMEOUT_ERROR` of the node. Defaults to [`DEFAULT_RPC_RETRIES`].
+    ///
+    /// Errors of the request itself (bad params, unknown method, contract panic) are never retried,
+    /// and neither is `sandbox_fast_forward`, as it's not safe to apply twice.
+    pub rpc_retries: Option<usize>,
+    /// Extra environment variables of the neard process, e.g. `RUST_BACKTRACE=1` to debug panics.
+    ///
+    /// They are applied after the log vars, so `RUST_LOG` set here takes precedence over `NEAR_SANDBOX_LOG`.
+    pub env: HashMap<String, String>,
+    /// Command to run neard through, e.g. `["taskset", "-c", "0-3"]` or `["valgrind"]`.
+    ///
+    /// The first element is the program, the rest of the elements are followed by the neard path and args,
+    /// so an empty wrapper is rejected.
+    /// The wrapper has to run neard in the same process (like `taskset` or `valgrind` do) or forward
+    /// the signals to it, otherwise neard won't be stopped when the sandbox is dropped.
+    pub command_wrapper: Option<Vec<String>>,
+    /// Capture the stderr of neard instead of inheriting it, so it can be read with
+    /// [`crate::Sandbox::logs`] and [`crate::Sandbox::logs_since`].
+    ///
+    /// Most of the logs are suppressed unless `NEAR_ENABLE_SANDBOX_LOG=1` is set.
+    pub capture_logs: bool,
+    /// Maximum size of the logs kept in memory with [`Self::capture_logs`], the oldest lines are
+    /// dropped once it's exceeded. Defaults to [`DEFAULT_LOG_BUFFER_BYTES`].
+    ///
+    /// Keeps the memory bounded for sandboxes living through a whole test suite.
+    pub log_buffer_bytes: Option<usize>,
+    /// Delete the cached neard binary and download it again before starting the sandbox.
+    ///
+    /// Useful to recover from a corrupted binary, e.g. left by an interrupted download.
+    /// The binary is re-downloaded at most once per process.
+    /// Can also be enabled with the `NEAR_SANDBOX_FORCE_DOWNLOAD=1` environment variable.
+    /// Has no effect when `NEAR_SANDBOX_BIN_PATH` is set.
+    pub force_download: bool,
+    /// HTTP client used for all the requests: the RPC calls, the imports from other networks and the
+    /// download of the neard binary.
+    ///
+    /// Useful to route the requests through a proxy or to trust custom TLS roots. Defaults to an agent
+    /// with the default `ureq` settings. The binary is downloaded at most once per process, so
+    /// the agent of the first sandbox started is used for it.
+    ///
+    /// The agent also sends the requests to the local RPC of the sandbox on `127.0.0.1`, so a proxy
+    /// set on it must let the loopback traffic through, or the sandbox never becomes reachable.
+    pub http_agent: Option<ureq::Agent>,
+    /// Setup routine that runs once the sandbox is ready, before it's returned to the caller
+    pub on_ready: Option<OnReady>,
+    /// Readiness checks forced to time out, to test the startup retries
+    #[cfg(any(test, feature = "__fault_injection"))]
+    #[doc(hidden)]
+    pub forced_startup_timeouts: Option<crate::fault_injection::StartupTimeouts>,
 }
 
-/// Overwrite the $home_dir/config.json file over a set of entries. `value` will be used per (key, value) pair
-/// where value can also be another dict. This recursively sets all entry in `value` dict to the config
-/// dict, and saves back into `home_dir` at the end of the day.
-fn overwrite(home_dir: impl AsRef<Path>, value: Value) -> Result<(), SandboxConfigError> {
-    let home_dir = home_dir.as_ref();
-    let config_file =
-        File::open(home_dir.join("config.json")).map_err(SandboxConfigError::FileError)?;
-    let config = BufReader::new(config_file);
-    let mut config: Value = serde_json::from_reader(config)?;
+impl SandboxConfig {
+    /// Lowest port that can be configured. Well-known ports need elevated privileges
+    /// and are likely taken by system services.
+    const MIN_PORT: u16 = 1024;
+
+    /// Whether the next readiness check has to time out, see [`Self::forced_startup_timeouts`]
+    pub(crate) fn take_forced_startup_timeout(&self) -> bool {
+        #[cfg(any(test, feature = "__fault_injection"))]
+        if let Some(timeouts) = &self.forced_startup_timeouts {
+            return timeouts.take();
+        }
+        false
+    }
+
+    /// Catch the settings that would make the sandbox fail cryptically or behave unexpectedly
+    pub(crate) fn validate(&self) -> Result<(), SandboxConfigError> {
+        self.validate_ports()?;
+
+        if self.command_wrapper.as_ref().is_some_and(Vec::is_empty) {
+            return Err(SandboxConfigError::CommandWrapperError(
+                "`command_wrapper` is empty, set it to `None` to run neard directly".to_owned(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Catch the port settings that would make neard fail cryptically after init
+    pub(crate) fn validate_ports(&self) -> Result<(), SandboxConfigError> {
+        for (name, port) in [("rpc_port", self.rpc_port), ("net_port", self.net_port)] {
+            if let Some(port) = port {
+                if port < Self::MIN_PORT {
+                    return Err(SandboxConfigError::PortError(format!(
+                        "`{name}` is {port}, but it must be at least {}",
+                        Self::MIN_PORT
+                    )));
+                }
+            }
+        }
+
+        if let (Some(rpc_port), Some(net_port)) = (self.rpc_port, self.net_port) {
+            if rpc_port == net_port {
+                return Err(SandboxConfigError::PortError(format!(
+                    "`rpc_port` and `net_port` are both {rpc_port}, but must be different"
+                )));
+            }
+        }
+
+        if let Some(range) = &self.rpc_port_range {
+            if range.is_empty() || *range.start() < Self::MIN_PORT {
+                return Err(SandboxConfigError::PortError(format!(
+                    "`rpc_port_range` is {range:?}, but it must be a non-empty range starting at {} or higher",
+                    Self::MIN_PORT
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Typed subset of the `store` section of `config.json`.
+///
+/// These settings only affect the performance of the node, so they are safe to change for the sandbox.
+/// Note that nearcore doesn't expose RocksDB write buffer sizes, so they can't be configured.
+/// Everything else in `store` can still be set with [`SandboxConfig::additional_config`].
+#[derive(Debug, Clone, Default)]
+pub struct StoreConfig {
+    /// Collect RocksDB statistics. Maps to `store.enable_statistics`
+    pub enable_statistics: Option<bool>,
+    /// Trie cache size per shard in bytes. Maps to `store.trie_cache.default_max_bytes`
+    pub trie_cache_default_max_bytes: Option<u64>,
+    /// Trie cache size in bytes for specific shards, keyed by the shard UId (e.g. `s0.v3`).
+    /// Maps to `store.trie_cache.per_shard_max_bytes`
+    pub trie_cache_per_shard_max_bytes: Vec<(String, u64)>,
+    /// Size of the RocksDB block cache for the `State` column in bytes. Maps to `store.col_state_cache_size`
+    pub col_state_cache_size: Option<u64>,
+    /// RocksDB block size in bytes. Maps to `store.block_size`
+    pub block_size: Option<u64>,
+}
+
+impl StoreConfig {
+    fn to_json(&self) -> Value {
+        let mut store = serde_json::json!({});
+        if let Some(enable_statistics) = self.enable_statistics {
+            store["enable_statistics"] = serde_json::json!(enable_statistics);
+        }
+        if let Some(default_max_bytes) = self.trie_cache_default_max_bytes {
+            store["trie_cache"]["default_max_bytes"] = serde_json::json!(default_max_bytes);
+        }
+        if !self.trie_cache_per_shard_max_bytes.is_empty() {
+            store["trie_cache"]["per_shard_max_bytes"] = self
+                .trie_cache_per_shard_max_bytes
+                .iter()
+                .map(|(shard_uid, max_bytes)| (shard_uid.clone(), serde_json::json!(max_bytes)))
+                .collect::<serde_json::Map<_, _>>()
+                .into();
+        }
+        if let Some(col_state_cache_size) = self.col_state_cache_size {
+            store["col_state_cache_size"] = serde_json::json!(col_state_cache_size);
+        }
+        if let Some(block_size) = self.block_size {
+            store["block_size"] = serde_json::json!(block_size);
+        }
+        store
+    }
+}
+
+/// Typed economics parameters of `genesis.json`.
+///
+/// Rates are `(numerator, denominator)` fractions, written the way nearcore expects them (`[numerator, denominator]`).
+/// Anything else can still be set with [`SandboxConfig::additional_genesis`], which takes precedence.
+#[derive(Debug, Clone, Default)]
+pub struct EconomicsConfig {
+    /// Share of the epoch reward sent to the treasury. Maps to `protocol_reward_rate`
+    pub protocol_reward_rate: Option<(i32, i32)>,
+    /// Maximum yearly inflation of the total supply. Maps to `max_inflation_rate`
+    pub max_inflation_rate: Option<(i32, i32)>,
+    /// Account receiving the protocol reward. Maps to `protocol_treasury_account`
+    pub protocol_treasury_account: Option<AccountId>,
+}
+
+impl EconomicsConfig {
+    fn to_json(&self) -> Result<Value, SandboxConfigError> {
+        let mut genesis = serde_json::json!({});
+        for (key, rate) in [
+            ("protocol_reward_rate", self.protocol_reward_rate),
+            ("max_inflation_rate", self.max_inflation_rate),
+        ] {
+            if let Some((numerator, denominator)) = rate {
+                if numerator < 0 || denominator <= 0 {
+                    return Err(SandboxConfigError::GenesisError(format!(
+                        "`{key}` must be a non-negative fraction with a positive denominator, got {numerator}/{denominator}"
+                    )));
+                }
+                genesis[key] = serde_json::json!([numerator, denominator]);
+            }
+        }
+        if let Some(treasury) = &self.protocol_treasury_account {
+            genesis["protocol_treasury_account"] = serde_json::json!(treasury);
+        }
+        Ok(genesis)
+    }
+}
+
+/// Typed validator seat parameters of `genesis.json`.
+///
+/// Seats are upper bounds: nearcore picks the validators for them among the ones staked in the
+/// `validators` of the genesis, which has a single validator by default. Values above the number of
+/// genesis validators don't add any validators, so they have to be added to the genesis
+/// (e.g. with [`SandboxConfig::additional_genesis`]) to test multi-validator setups.
+/// Anything else can still be set with [`SandboxConfig::additional_genesis`], which takes precedence.
+#[derive(Debug, Clone, Default)]
+pub struct ConsensusConfig {
+    /// Maximum number of block producers. Maps to `num_block_producer_seats`
+    pub num_block_producer_seats: Option<u64>,
+    /// Maximum number of block producers per shard. Maps to `num_block_producer_seats_per_shard`
+    pub num_block_producer_seats_per_shard: Option<Vec<u64>>,
+    /// Maximum number of chunk producers. Maps to `num_chunk_producer_seats`
+    pub num_chunk_producer_seats: Option<u64>,
+    /// Maximum number of chunk validators. Maps to `num_chunk_validator_seats`
+    pub num_chunk_validator_seats: Option<u64>,
+    /// Minimum number of validators per shard, can't exceed the number of genesis validators.
+    /// Maps to `minimum_validators_per_shard`
+    pub minimum_validators_per_shard: Option<u64>,
+}
+
+impl ConsensusConfig {
+    fn to_json(&self) -> Result<Value, SandboxConfigError> {
+        let mut genesis = serde_json::json!({});
+        for (key, seats) in [
+            ("num_block_producer_seats", self.num_block_producer_seats),
+            ("num_chunk_producer_seats", self.num_chunk_producer_seats),
+            ("num_chunk_validator_seats", self.num_chunk_validator_seats),
+            (
+                "minimum_validators_per_shard",
+                self.minimum_validators_per_shard,
+            ),
+        ] {
+            if let Some(seats) = seats {
+                if seats == 0 {
+                    return Err(SandboxConfigError::GenesisError(format!(
+                        "`{key}` must be positive, otherwise no blocks are produced"
+                    )));
+                }
+                genesis[key] = serde_json::json!(seats);
+            }
+        }
+        if let Some(seats_per_shard) = &self.num_block_producer_seats_per_shard {
+            if seats_per_shard.is_empty() || seats_per_shard.contains(&0) {
+                return Err(SandboxConfigError::GenesisError(
+                    "`num_block_producer_seats_per_shard` must have a positive number of seats for every shard"
+                        .to_owned(),
+                ));
+            }
+            genesis["num_block_producer_seats_per_shard"] = serde_json::json!(seats_per_shard);
+        }
+        Ok(genesis)
+    }
+}
+
+/// Future returned by the [`OnReady`] callback
+pub type OnReadyFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<(), Box<dyn std::error::Error + Send + Sync>>> + Send + 'a>>;
+
+/// Callback invoked with the freshly started [`Sandbox`] before `start_sandbox*` returns.
+///
+/// Useful to bake fixture setup (deploying contracts, creating accounts) into the startup, so
+/// every sandbox started with the same [`SandboxConfig`] is pre-seeded in the same way.
+/// If the callback fails, the sandbox is shut down and [`SandboxError::SetupError`](crate::error_kind::SandboxError::SetupError) is returned.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use near_sandbox::*;
+/// use near_sandbox::config::OnReady;
+///
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let config = SandboxConfig {
+///     on_ready: Some(OnReady::new(|sandbox| {
+///         Box::pin(async move {
+///             sandbox.create_account("alice.sandbox".parse()?).send().await?;
+///             Ok(())
+///         })
+///     })),
+///     ..Default::default()
+/// };
+///
+/// let sandbox = Sandbox::start_sandbox_with_config(config).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct OnReady(Arc<dyn for<'a> Fn(&'a Sandbox) -> OnReadyFuture<'a> + Send + Sync>);
+
+impl OnReady {
+    pub fn new<F>(callback: F) -> Self
+    where
+        F: for<'a> Fn(&'a Sandbox) -> OnReadyFuture<'a> + Send + Sync + 'static,
+    {
+        Self(Arc::new(callback))
+    }
 
-    json_patch::merge(&mut config, &value);
-    let config_file =
-        File::create(home_dir.join("config.json")).map_err(SandboxConfigError::FileError)?;
-    serde_json::to_writer(config_file, &config)?;
+    pub(crate) fn call<'a>(&self, sandbox: &'a Sandbox) -> OnReadyFuture<'a> {
+        (self.0)(sandbox)
+    }
+}
+
+impl std::fmt::Debug for OnReady {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("OnReady(..)")
+    }
+}
+
+fn read_json(path: &Path) -> Result<Value, SandboxConfigError> {
+    let file = File::open(path).map_err(SandboxConfigError::FileError)?;
+    Ok(serde_json::from_reader(BufReader::new(file))?)
+}
 
+fn write_json(path: &Path, value: &Value) -> Result<(), SandboxConfigError> {
+    let file = File::create(path).map_err(SandboxConfigError::FileError)?;
+    serde_json::to_writer(file, value)?;
     Ok(())
 }
 
@@ -225,19 +733,51 @@ where
     }
 }
 
-/// Set extra configs for the sandbox with custom configuration.
+/// Default `json_payload_max_size` of nearcore, used for the sandboxes that are not started by this crate
+pub(crate) const NEARCORE_MAX_PAYLOAD_SIZE: usize = 10 * 1024 * 1024;
+
+/// Maximum size of the JSON RPC requests the sandbox accepts, including the override in
+/// [`SandboxConfig::additional_config`].
+pub(crate) fn max_payload_size(config: &SandboxConfig) -> usize {
+    config
+        .additional_config
+        .as_ref()
+        .and_then(|additional| additional.pointer("/rpc/limits_config/json_payload_max_size"))
+        .and_then(|size| size.as_u64())
+        .and_then(|size| usize::try_from(size).ok())
+        .or(config.max_payload_size)
+        .or_else(|| parse_env("NEAR_SANDBOX_MAX_PAYLOAD_SIZE").ok().flatten())
+        .unwrap_or(1024 * 1024 * 1024) // Default to 1GB
+}
+
+/// Apply the sandbox configuration to the `config.json` and `genesis.json` generated by neard in `home_dir`.
 ///
-/// # Arguments
-/// * `home_dir` - path for home directory of neard
-/// * `config` - config, with which neard configuration will be overwritten
-pub(crate) fn set_sandbox_configs_with_config(
+/// Both files are read once, all the changes are made in memory and the files are written once at the end.
+pub(crate) fn set_sandbox_home_with_config(
     home_dir: impl AsRef<Path>,
     config: &SandboxConfig,
 ) -> Result<(), SandboxConfigError> {
-    let max_payload_size = config
-        .max_payload_size
-        .or_else(|| parse_env("NEAR_SANDBOX_MAX_PAYLOAD_SIZE").ok().flatten())
-        .unwrap_or(1024 * 1024 * 1024); // Default to 1GB
+    let home_dir = home_dir.as_ref();
+    let node_config = match &config.config_override {
+        Some(ConfigOverride::File(path)) => read_json(path)?,
+        Some(ConfigOverride::Json(node_config)) => node_config.clone(),
+        None => {
+            let mut node_config = read_json(&home_dir.join("config.json"))?;
+            apply_sandbox_configs(&mut node_config, config);
+            node_config
+        }
+    };
+    let mut genesis = read_json(&home_dir.join("genesis.json"))?;
+    apply_sandbox_genesis(&mut genesis, config)?;
+
+    write_json(&home_dir.join("config.json"), &node_config)?;
+    write_json(&home_dir.join("genesis.json"), &genesis)?;
+    save_account_keys(home_dir, &key_file_accounts(config))
+}
+
+/// Merge the extra configs of the sandbox into the parsed neard `config.json`.
+fn apply_sandbox_configs(node_config: &mut Value, config: &SandboxConfig) {
+    let max_payload_size = max_payload_size(config);
 
     let max_open_files = config
         .max_open_files
@@ -255,35 +795,33 @@ pub(crate) fn set_sandbox_configs_with_config(
         }
     });
 
+    if let Some(max_gas_burnt_view) = config.max_gas_burnt_view {
+        json_config["max_gas_burnt_view"] = serde_json::json!(max_gas_burnt_view);
+    }
+    if let Some(track_all_shards) = config.track_all_shards {
+        json_config["tracked_shards_config"] = if track_all_shards {
+            "AllShards".into()
+        } else {
+            // The validator still tracks the shards it produces chunks for
+            "NoShards".into()
+        };
+    }
+    json_patch::merge(&mut json_config["store"], &config.store.to_json());
+
     // Merge any additional config provided by the user
     if let Some(additional_config) = &config.additional_config {
         json_patch::merge(&mut json_config, additional_config);
     }
 
-    overwrite(home_dir, json_config)
+    json_patch::merge(node_config, &json_config);
 }
 
-/// Overwrite the $home_dir/genesis.json file over a set of entries. `value` will be used per (key, value) pair
-/// where value can also be another dict. This recursively sets all entry in `value` dict to the config
-/// dict, and saves back into `home_dir` at the end of the day.
-fn overwrite_genesis(
-    home_dir: impl AsRef<Path>,
+/// Add the sandbox accounts and settings to the parsed neard `genesis.json`.
+fn apply_sandbox_genesis(
+    genesis: &mut Value,
     config: &SandboxConfig,
 ) -> Result<(), SandboxConfigError> {
-    let home_dir = home_dir.as_ref();
-    let config_file =
-        File::open(home_dir.join("genesis.json")).map_err(SandboxConfigError::FileError)?;
-    let config_reader = BufReader::new(config_file);
-    let mut genesis: Value = serde_json::from_reader(config_reader)?;
     let genesis_obj = genesis.as_object_mut().expect("expected to be object");
-    let mut total_supply = u128::from_str(
-        genesis_obj
-            .get_mut("total_supply")
-            .expect("expected exist total_supply")
-            .as_str()
-            .unwrap_or_default(),
-    )
-    .unwrap_or_default();
 
     let mut accounts_to_add = vec![
         GenesisAccount::default(),
@@ -292,59 +830,190 @@ fn overwrite_genesis(
 
     accounts_to_add.extend(config.additional_accounts.clone());
 
-    for account in &accounts_to_add {
-        total_supply += account.balance.as_yoctonear();
-    }
-
-    genesis_obj.insert(
-        "total_supply".to_string(),
-        Value::String(total_supply.to_string()),
-    );
-
     let records = genesis_obj
         .get_mut("records")
         .expect("expect exist records");
     let records_array = records.as_array_mut().expect("expected to be array");
 
     for account in &accounts_to_add {
-        records_array.push(serde_json::json!(
-            {
-                "Account": {
-                    "account_id": account.account_id,
-                    "account": {
-                    "amount": account.balance,
-                    "locked": "0",
-                    "code_hash": "11111111111111111111111111111111",
-                    "storage_usage": 182
-                    }
-                }
-            }
-        ));
+        for record in account.state_records() {
+            records_array.push(serde_json::to_value(record)?);
+        }
+    }
 
-        records_array.push(serde_json::json!(
-            {
-                "AccessKey": {
-                    "account_id": account.account_id,
-                    "public_key": account.public_key,
-                    "access_key": {
-                    "nonce": 0,
-                    "permission": "FullAccess"
-                    }
-                }
-            }
-        ));
+    if let Some(genesis_time) = config.genesis_time {
+        genesis_obj.insert(
+            "genesis_time".to_string(),
+            Value::String(format_genesis_time(genesis_time)?),
+        );
+    } else if config.deterministic {
+        genesis_obj.insert(
+            "genesis_time".to_string(),
+            Value::String(DETERMINISTIC_GENESIS_TIME.to_string()),
+        );
+    }
+    if let Some(gas_limit) = config.gas_limit {
+        genesis_obj.insert("gas_limit".to_string(), serde_json::json!(gas_limit));
+    }
+    if let Some(transaction_validity_period) = config.transaction_validity_period {
+        genesis_obj.insert(
+            "transaction_validity_period".to_string(),
+            serde_json::json!(transaction_validity_period),
+        );
     }
 
+    json_patch::merge(genesis, &config.economics.to_json()?);
+    json_patch::merge(genesis, &config.consensus.to_json()?);
+
     if let Some(additional_genesis) = &config.additional_genesis {
-        json_patch::merge(&mut genesis, additional_genesis);
+        json_patch::merge(genesis, additional_genesis);
+    }
+    validate_genesis(genesis)?;
+
+    // NOTE: `total_supply` has to match the sum of all the balances in the final records, so it's
+    // computed after `additional_genesis` is merged, as it can replace records as well.
+    // An explicit `total_supply` provided by the user is kept as is.
+    let supply_overridden = config
+        .additional_genesis
+        .as_ref()
+        .is_some_and(|genesis| genesis.get("total_supply").is_some());
+    if !supply_overridden {
+        let total_supply = records_total_supply(&genesis["records"])?;
+        genesis["total_supply"] = Value::String(total_supply.to_string());
     }
 
-    let config_file =
-        File::create(home_dir.join("genesis.json")).map_err(SandboxConfigError::FileError)?;
-    serde_json::to_writer(config_file, &genesis)?;
     Ok(())
 }
 
+/// JSON type of a `genesis.json` field, checked before neard is launched with the genesis
+#[derive(Debug, Clone, Copy)]
+enum GenesisFieldKind {
+    String,
+    Integer,
+    /// Amount of yoctoNEAR, a decimal `u128` in a string
+    Balance,
+    Array,
+}
+
+impl GenesisFieldKind {
+    fn matches(self, value: &Value) -> bool {
+        match self {
+            Self::String => value.is_string(),
+            Self::Integer => value.is_u64(),
+            Self::Balance => value.as_str().is_some_and(|b| u128::from_str(b).is_ok()),
+            Self::Array => value.is_array(),
+        }
+    }
+
+    const fn description(self) -> &'static str {
+        match self {
+            Self::String => "a string",
+            Self::Integer => "a non-negative integer",
+            Self::Balance => "a yoctoNEAR amount in a string",
+            Self::Array => "an array",
+        }
+    }
+}
+
+/// Top-level fields of `genesis.json` neard can't start without
+const REQUIRED_GENESIS_FIELDS: &[(&str, GenesisFieldKind)] = &[
+    ("chain_id", GenesisFieldKind::String),
+    ("genesis_time", GenesisFieldKind::String),
+    ("genesis_height", GenesisFieldKind::Integer),
+    ("protocol_version", GenesisFieldKind::Integer),
+    ("epoch_length", GenesisFieldKind::Integer),
+    ("gas_limit", GenesisFieldKind::Integer),
+    ("num_block_producer_seats", GenesisFieldKind::Integer),
+    ("transaction_validity_period", GenesisFieldKind::Integer),
+    ("min_gas_price", GenesisFieldKind::Balance),
+    ("total_supply", GenesisFieldKind::Balance),
+    ("validators", GenesisFieldKind::Array),
+    ("records", GenesisFieldKind::Array),
+];
+
+/// Catch the genesis broken by `additional_genesis` before the neard launch, which would fail with an init
+/// error or a readiness timeout instead. Names all the missing and mistyped fields at once.
+fn validate_genesis(genesis: &Value) -> Result<(), SandboxConfigError> {
+    let mut problems = Vec::new();
+    for &(field, kind) in REQUIRED_GENESIS_FIELDS {
+        match genesis.get(field) {
+            None | Some(Value::Null) => problems.push(format!("`{field}` is missing")),
+            Some(value) if !kind.matches(value) => {
+                problems.push(format!(
+                    "`{field}` must be {}, got `{value}`",
+                    kind.description()
+                ));
+            }
+            Some(_) => {}
+        }
+    }
+    if genesis["validators"].as_array().is_some_and(Vec::is_empty) {
+        problems.push("`validators` must have at least one validator".to_owned());
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(SandboxConfigError::GenesisError(problems.join(", ")))
+    }
+}
+
+/// Format the time as RFC 3339 UTC timestamp with nanoseconds, the way nearcore writes `genesis_time`
+fn format_genesis_time(time: SystemTime) -> Result<String, SandboxConfigError> {
+    let since_epoch = time.duration_since(UNIX_EPOCH).map_err(|_| {
+        SandboxConfigError::GenesisError("`genesis_time` can't be before the unix epoch".to_owned())
+    })?;
+    let secs = since_epoch.as_secs();
+    let (days, secs_of_day) = (secs / 86_400, secs % 86_400);
+
+    // Civil date from the number of days since the epoch: https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+
+    Ok(format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:09}Z",
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60,
+        since_epoch.subsec_nanos()
+    ))
+}
+
+/// Sum of liquid and locked balances of all the account records
+fn records_total_supply(records: &Value) -> Result<u128, SandboxConfigError> {
+    let records = records.as_array().ok_or_else(|| {
+        SandboxConfigError::GenesisError("`records` is expected to be an array".to_owned())
+    })?;
+
+    let mut total_supply: u128 = 0;
+    for account in records.iter().filter_map(|record| record.get("Account")) {
+        for field in ["amount", "locked"] {
+            let balance = account["account"][field]
+                .as_str()
+                .and_then(|balance| u128::from_str(balance).ok())
+                .ok_or_else(|| {
+                    SandboxConfigError::GenesisError(format!(
+                        "account {} has invalid `{field}` balance",
+                        account["account_id"]
+                    ))
+                })?;
+            total_supply = total_supply.checked_add(balance).ok_or_else(|| {
+                SandboxConfigError::GenesisError("total supply overflows u128".to_owned())
+            })?;
+        }
+    }
+
+    Ok(total_supply)
+}
+
 /// Save account keys to individual JSON files
 fn save_account_keys(
     home_dir: impl AsRef<Path>,
@@ -372,6 +1041,54 @@ fn save_account_keys(
     Ok(())
 }
 
+#[derive(Deserialize)]
+pub(crate) struct AccountKeyFile {
+    pub account_id: AccountId,
+    pub public_key: String,
+    pub private_key: String,
+}
+
+/// Read the key file written by [`save_account_keys`].
+///
+/// Returns `None` if the file doesn't exist or it's not an account key file
+/// (e.g. `genesis.json` or `validator_key.json` in the same directory).
+pub(crate) fn read_account_key_file(
+    path: impl AsRef<Path>,
+) -> Result<Option<AccountKeyFile>, SandboxConfigError> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(SandboxConfigError::FileError(e)),
+    };
+
+    Ok(serde_json::from_reader(BufReader::new(file)).ok())
+}
+
+/// Ids of the accounts in the `records` of `$home_dir/genesis.json`, sorted and deduplicated
+pub(crate) fn read_genesis_account_ids(
+    home_dir: impl AsRef<Path>,
+) -> Result<Vec<AccountId>, SandboxConfigError> {
+    let genesis = read_json(&home_dir.as_ref().join("genesis.json"))?;
+    let records = genesis["records"].as_array().ok_or_else(|| {
+        SandboxConfigError::GenesisError("`records` is expected to be an array".to_owned())
+    })?;
+
+    let account_ids = records
+        .iter()
+        .filter_map(|record| record.get("Account"))
+        .map(|account| {
+            serde_json::from_value(account["account_id"].clone()).map_err(|_| {
+                SandboxConfigError::GenesisError(format!(
+                    "invalid account id {} in the records",
+                    account["account_id"]
+                ))
+            })
+        })
+        .collect::<Result<BTreeSet<_>, _>>()?;
+
+    Ok(account_ids.into_iter().collect())
+}
+
 pub fn set_sandbox_genesis(home_dir: impl AsRef<Path>) -> Result<(), SandboxConfigError> {
     let config = SandboxConfig::default();
     set_sandbox_genesis_with_config(&home_dir, &config)
@@ -381,12 +1098,252 @@ pub fn set_sandbox_genesis_with_config(
     home_dir: impl AsRef<Path>,
     config: &SandboxConfig,
 ) -> Result<(), SandboxConfigError> {
-    overwrite_genesis(&home_dir, config)?;
+    let home_dir = home_dir.as_ref();
+    let mut genesis = read_json(&home_dir.join("genesis.json"))?;
+    apply_sandbox_genesis(&mut genesis, config)?;
+    write_json(&home_dir.join("genesis.json"), &genesis)?;
+
+    save_account_keys(home_dir, &key_file_accounts(config))
+}
 
+/// Accounts whose keys are saved next to the genesis, so they can be looked up by account id
+fn key_file_accounts(config: &SandboxConfig) -> Vec<GenesisAccount> {
     let mut all_accounts = vec![GenesisAccount::default()];
     all_accounts.extend(config.additional_accounts.clone());
+    all_accounts
+}
 
-    save_account_keys(&home_dir, &all_accounts)?;
+#[cfg(test)]
+mod tests {
+    use std::ops::RangeInclusive;
+    use std::time::{Duration, UNIX_EPOCH};
 
-    Ok(())
+    use super::{
+        ConfigOverride, ConsensusConfig, EconomicsConfig, SandboxConfig, apply_sandbox_configs,
+        apply_sandbox_genesis, format_genesis_time, set_sandbox_home_with_config,
+    };
+    use crate::error_kind::SandboxConfigError;
+
+    #[test]
+    fn test_format_genesis_time() {
+        assert_eq!(
+            format_genesis_time(UNIX_EPOCH).unwrap(),
+            "1970-01-01T00:00:00.000000000Z"
+        );
+        assert_eq!(
+            format_genesis_time(UNIX_EPOCH + Duration::new(1_709_251_199, 42)).unwrap(),
+            "2024-02-29T23:59:59.000000042Z"
+        );
+        assert!(format_genesis_time(UNIX_EPOCH - Duration::from_secs(1)).is_err());
+    }
+
+    #[test]
+    fn test_validate_ports() {
+        let config = |rpc_port, net_port| SandboxConfig {
+            rpc_port,
+            net_port,
+            ..Default::default()
+        };
+
+        assert!(config(None, None).validate_ports().is_ok());
+        assert!(config(Some(3030), Some(3031)).validate_ports().is_ok());
+        assert!(config(Some(3030), Some(3030)).validate_ports().is_err());
+        assert!(config(Some(80), None).validate_ports().is_err());
+        assert!(config(None, Some(0)).validate_ports().is_err());
+
+        let config = SandboxConfig {
+            rpc_port_range: Some(RangeInclusive::new(4000, 3000)),
+            ..Default::default()
+        };
+        assert!(config.validate_ports().is_err());
+    }
+
+    #[test]
+    fn test_validate_command_wrapper() {
+        let config = |command_wrapper| SandboxConfig {
+            command_wrapper,
+            ..Default::default()
+        };
+
+        assert!(config(None).validate().is_ok());
+        assert!(config(Some(vec!["taskset".to_owned()])).validate().is_ok());
+        assert!(matches!(
+            config(Some(vec![])).validate(),
+            Err(SandboxConfigError::CommandWrapperError(_))
+        ));
+    }
+
+    #[test]
+    fn test_economics_to_json() {
+        let economics = EconomicsConfig {
+            protocol_reward_rate: Some((1, 10)),
+            max_inflation_rate: None,
+            protocol_treasury_account: Some("treasury.sandbox".parse().unwrap()),
+        };
+        assert_eq!(
+            economics.to_json().unwrap(),
+            serde_json::json!({
+                "protocol_reward_rate": [1, 10],
+                "protocol_treasury_account": "treasury.sandbox",
+            })
+        );
+
+        let economics = EconomicsConfig {
+            max_inflation_rate: Some((1, 0)),
+            ..Default::default()
+        };
+        assert!(economics.to_json().is_err());
+    }
+
+    #[test]
+    fn test_apply_in_memory() {
+        let config = SandboxConfig {
+            gas_limit: Some(1_000),
+            additional_config: Some(serde_json::json!({ "store": { "max_open_files": 10 } })),
+            ..Default::default()
+        };
+
+        let mut node_config = serde_json::json!({ "rpc": { "addr": "0.0.0.0:3030" } });
+        apply_sandbox_configs(&mut node_config, &config);
+        assert_eq!(node_config["rpc"]["addr"], "0.0.0.0:3030");
+        assert_eq!(node_config["store"]["max_open_files"], 10);
+        // Left as generated by `neard init`
+        assert!(node_config.get("tracked_shards_config").is_none());
+
+        for (track_all_shards, tracked_shards_config) in [(true, "AllShards"), (false, "NoShards")]
+        {
+            let mut node_config = serde_json::json!({});
+            apply_sandbox_configs(
+                &mut node_config,
+                &SandboxConfig {
+                    track_all_shards: Some(track_all_shards),
+                    ..Default::default()
+                },
+            );
+            assert_eq!(node_config["tracked_shards_config"], tracked_shards_config);
+        }
+
+        let mut genesis = init_genesis();
+        apply_sandbox_genesis(&mut genesis, &config).unwrap();
+        assert_eq!(genesis["gas_limit"], 1_000);
+        assert!(!genesis["records"].as_array().unwrap().is_empty());
+        assert!(genesis["total_supply"].as_str().unwrap() != "0");
+    }
+
+    #[test]
+    fn test_runtime_limits() {
+        let config = SandboxConfig {
+            max_gas_burnt_view: Some(1_000),
+            gas_limit: Some(2_000),
+            transaction_validity_period: Some(42),
+            ..Default::default()
+        };
+
+        let mut node_config = serde_json::json!({});
+        apply_sandbox_configs(&mut node_config, &config);
+        assert_eq!(node_config["max_gas_burnt_view"], 1_000);
+
+        let mut genesis = init_genesis();
+        apply_sandbox_genesis(&mut genesis, &config).unwrap();
+        assert_eq!(genesis["gas_limit"], 2_000);
+        assert_eq!(genesis["transaction_validity_period"], 42);
+    }
+
+    /// Fields of the genesis written by `neard init` that are checked before the launch
+    fn init_genesis() -> serde_json::Value {
+        serde_json::json!({
+            "chain_id": "localnet",
+            "genesis_time": "2024-01-01T00:00:00.000000000Z",
+            "genesis_height": 0,
+            "protocol_version": 80,
+            "epoch_length": 500,
+            "gas_limit": 1,
+            "num_block_producer_seats": 1,
+            "transaction_validity_period": 100,
+            "min_gas_price": "100000000",
+            "total_supply": "0",
+            "validators": [{ "account_id": "test.near", "amount": "1" }],
+            "records": [],
+        })
+    }
+
+    #[test]
+    fn test_config_override() {
+        let home_dir = tempfile::tempdir().unwrap();
+        let write = |name: &str, value: &serde_json::Value| {
+            std::fs::write(home_dir.path().join(name), value.to_string()).unwrap();
+        };
+        let read = |name: &str| -> serde_json::Value {
+            serde_json::from_slice(&std::fs::read(home_dir.path().join(name)).unwrap()).unwrap()
+        };
+        let custom = serde_json::json!({ "store": { "max_open_files": 1 } });
+
+        write("config.json", &serde_json::json!({}));
+        write("genesis.json", &init_genesis());
+        set_sandbox_home_with_config(
+            home_dir.path(),
+            &SandboxConfig {
+                config_override: Some(ConfigOverride::Json(custom.clone())),
+                max_open_files: Some(10),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(read("config.json"), custom);
+
+        let custom_file = home_dir.path().join("custom.json");
+        std::fs::write(&custom_file, custom.to_string()).unwrap();
+        write("config.json", &serde_json::json!({}));
+        write("genesis.json", &init_genesis());
+        set_sandbox_home_with_config(
+            home_dir.path(),
+            &SandboxConfig {
+                config_override: Some(ConfigOverride::File(custom_file)),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(read("config.json"), custom);
+    }
+
+    #[test]
+    fn test_invalid_merged_genesis() {
+        let config = SandboxConfig {
+            additional_genesis: Some(serde_json::json!({
+                "epoch_length": "500",
+                "records": null,
+                "validators": [],
+            })),
+            ..Default::default()
+        };
+
+        let error = apply_sandbox_genesis(&mut init_genesis(), &config)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("`epoch_length` must be a non-negative integer, got `\"500\"`"));
+        assert!(error.contains("`records` is missing"));
+        assert!(error.contains("`validators` must have at least one validator"));
+    }
+
+    #[test]
+    fn test_consensus_to_json() {
+        let consensus = ConsensusConfig {
+            num_block_producer_seats: Some(2),
+            num_block_producer_seats_per_shard: Some(vec![2]),
+            ..Default::default()
+        };
+        assert_eq!(
+            consensus.to_json().unwrap(),
+            serde_json::json!({
+                "num_block_producer_seats": 2,
+                "num_block_producer_seats_per_shard": [2],
+            })
+        );
+
+        let consensus = ConsensusConfig {
+            num_chunk_producer_seats: Some(0),
+            ..Default::default()
+        };
+        assert!(consensus.to_json().is_err());
+    }
 }
@@ -6,6 +6,9 @@ pub enum SandboxError {
     #[error("{0}")]
     TcpError(#[from] TcpError),
 
+    #[error("{0}")]
+    RpcError(#[from] SandboxRpcError),
+
     #[error("Error while performing r/w operations on the file: {0}")]
     FileError(std::io::Error),
 
@@ -27,6 +30,11 @@ pub enum SandboxError {
     #[error("Download error: {0}")]
     DownloadError(String),
 
+    #[error(
+        "No sandbox binary is published for `{0}` yet, binaries of nearcore commits are usually built within 12h after they are pushed"
+    )]
+    ArtifactNotFound(String),
+
     #[error("Install error: {0}")]
     InstallError(String),
 
@@ -35,6 +43,15 @@ pub enum SandboxError {
 
     #[error("Unsupported platform: {0}")]
     UnsupportedPlatformError(String),
+
+    #[error("Sandbox init failed with {0}: {1}")]
+    InitError(std::process::ExitStatus, String),
+
+    #[error("Sandbox setup failed: {0}")]
+    SetupError(String),
+
+    #[error("Couldn't connect to the sandbox at {0}")]
+    ConnectionError(String),
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -47,6 +64,32 @@ pub enum SandboxRpcError {
 
     #[error("Sandbox RPC error: {0}")]
     SandboxRpcError(String),
+
+    #[error("Invalid state record at index {0}: {1}")]
+    InvalidStateRecord(usize, String),
+
+    #[error("Account state mismatch: {0}")]
+    AccountMismatch(Box<crate::sandbox::compare::AccountDiff>),
+
+    #[error("State patch was applied only partially, it's safe to retry: {0}")]
+    PatchPartiallyApplied(Box<SandboxRpcError>),
+
+    #[error("Failed to deserialize the view call result: {0}")]
+    ViewResultError(String),
+
+    #[error("Failed to serialize the arguments to JSON: {0}")]
+    ArgsSerializeError(String),
+
+    #[error("Failed to read the contract code from {0:?}: {1}")]
+    ContractCodeRead(std::path::PathBuf, std::io::Error),
+
+    #[error("No contract code to deploy to `{0}`")]
+    MissingContractCode(String),
+
+    #[error(
+        "State of {0} is too large for `view_state` of the RPC node, fetch it from a node with a higher `trie_viewer_state_size_limit`"
+    )]
+    ContractStateTooLarge(String),
 }
 
 impl From<ureq::Error> for SandboxRpcError {
@@ -55,6 +98,14 @@ impl From<ureq::Error> for SandboxRpcError {
     }
 }
 
+impl SandboxRpcError {
+    /// Whether the RPC rejected the request because the account doesn't exist
+    pub fn is_unknown_account(&self) -> bool {
+        // NOTE: RPC errors are kept as JSON strings, the error cause is `{"name": "UNKNOWN_ACCOUNT", ...}`
+        matches!(self, Self::SandboxRpcError(e) if e.contains("\"UNKNOWN_ACCOUNT\""))
+    }
+}
+
 #[derive(thiserror::Error, Debug)]
 #[non_exhaustive]
 pub enum TcpError {
@@ -72,6 +123,17 @@ pub enum TcpError {
 
     #[error("Error while locking port file: {0}")]
     LockingError(std::io::Error),
+
+    #[error("No free port in range {0:?}")]
+    PortRangeExhausted(std::ops::RangeInclusive<u16>),
+
+    #[error("Port lock directory {0:?} is not writable: {1}")]
+    LockDirError(std::path::PathBuf, std::io::Error),
+
+    #[error(
+        "Neither 127.0.0.1 nor ::1 can be bound to port {0}, the sandbox needs a loopback interface"
+    )]
+    LoopbackUnavailable(u16),
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -84,4 +146,146 @@ pub enum SandboxConfigError {
 
     #[error("Invalid environment variables: {0}")]
     EnvParseError(String),
+
+    #[error("Invalid genesis: {0}")]
+    GenesisError(String),
+
+    #[error("Invalid port configuration: {0}")]
+    PortError(String),
+
+    #[error("Invalid command wrapper: {0}")]
+    CommandWrapperError(String),
+}
+
+// NOTE: `std::io::Error`, `ureq::Error` and `serde_json::Error` are not `Clone`, so the clones
+// below keep only the kind and the message of the wrapped errors. The `source` chain is lost.
+
+fn clone_io_error(error: &std::io::Error) -> std::io::Error {
+    std::io::Error::new(error.kind(), error.to_string())
+}
+
+impl Clone for SandboxError {
+    /// Lossy clone: wrapped io errors keep only their kind and message
+    fn clone(&self) -> Self {
+        match self {
+            Self::SandboxConfigError(e) => Self::SandboxConfigError(e.clone()),
+            Self::TcpError(e) => Self::TcpError(e.clone()),
+            Self::RpcError(e) => Self::RpcError(e.clone()),
+            Self::FileError(e) => Self::FileError(clone_io_error(e)),
+            Self::RuntimeError(e) => Self::RuntimeError(clone_io_error(e)),
+            Self::ShutdownError(e) => Self::ShutdownError(clone_io_error(e)),
+            Self::TimeoutError => Self::TimeoutError,
+            Self::SandboxStartupRetriesExhausted(retries) => {
+                Self::SandboxStartupRetriesExhausted(*retries)
+            }
+            Self::BinaryError(e) => Self::BinaryError(e.clone()),
+            Self::DownloadError(e) => Self::DownloadError(e.clone()),
+            Self::ArtifactNotFound(version) => Self::ArtifactNotFound(version.clone()),
+            Self::InstallError(e) => Self::InstallError(e.clone()),
+            Self::SandboxVerificationError(e) => Self::SandboxVerificationError(e.clone()),
+            Self::UnsupportedPlatformError(e) => Self::UnsupportedPlatformError(e.clone()),
+            Self::InitError(status, stderr) => Self::InitError(*status, stderr.clone()),
+            Self::SetupError(e) => Self::SetupError(e.clone()),
+            Self::ConnectionError(e) => Self::ConnectionError(e.clone()),
+        }
+    }
+}
+
+impl Clone for SandboxRpcError {
+    /// Lossy clone: request errors other than HTTP status codes are turned into io errors with the same message,
+    /// wrapped io errors keep only their kind and message
+    fn clone(&self) -> Self {
+        match self {
+            Self::RequestError(e) => Self::RequestError(Box::new(match e.as_ref() {
+                ureq::Error::StatusCode(code) => ureq::Error::StatusCode(*code),
+                ureq::Error::Io(e) => ureq::Error::Io(clone_io_error(e)),
+                e => ureq::Error::Io(std::io::Error::other(e.to_string())),
+            })),
+            Self::UnexpectedResponse => Self::UnexpectedResponse,
+            Self::SandboxRpcError(e) => Self::SandboxRpcError(e.clone()),
+            Self::InvalidStateRecord(index, reason) => {
+                Self::InvalidStateRecord(*index, reason.clone())
+            }
+            Self::AccountMismatch(diff) => Self::AccountMismatch(diff.clone()),
+            Self::PatchPartiallyApplied(e) => Self::PatchPartiallyApplied(e.clone()),
+            Self::ViewResultError(e) => Self::ViewResultError(e.clone()),
+            Self::ArgsSerializeError(e) => Self::ArgsSerializeError(e.clone()),
+            Self::ContractCodeRead(path, e) => {
+                Self::ContractCodeRead(path.clone(), clone_io_error(e))
+            }
+            Self::MissingContractCode(account_id) => Self::MissingContractCode(account_id.clone()),
+            Self::ContractStateTooLarge(account_id) => {
+                Self::ContractStateTooLarge(account_id.clone())
+            }
+        }
+    }
+}
+
+impl Clone for TcpError {
+    /// Lossy clone: wrapped io errors keep only their kind and message
+    fn clone(&self) -> Self {
+        match self {
+            Self::SocketCreationError => Self::SocketCreationError,
+            Self::SocketSetReuseAddrError => Self::SocketSetReuseAddrError,
+            Self::BindError(port, e) => Self::BindError(*port, clone_io_error(e)),
+            Self::LocalAddrError(e) => Self::LocalAddrError(clone_io_error(e)),
+            Self::LockingError(e) => Self::LockingError(clone_io_error(e)),
+            Self::PortRangeExhausted(range) => Self::PortRangeExhausted(range.clone()),
+            Self::LockDirError(dir, e) => Self::LockDirError(dir.clone(), clone_io_error(e)),
+            Self::LoopbackUnavailable(port) => Self::LoopbackUnavailable(*port),
+        }
+    }
+}
+
+impl Clone for SandboxConfigError {
+    /// Lossy clone: wrapped io and json errors keep only their message
+    fn clone(&self) -> Self {
+        match self {
+            Self::FileError(e) => Self::FileError(clone_io_error(e)),
+            Self::JsonParseError(e) => {
+                Self::JsonParseError(<serde_json::Error as serde::de::Error>::custom(e))
+            }
+            Self::EnvParseError(e) => Self::EnvParseError(e.clone()),
+            Self::GenesisError(e) => Self::GenesisError(e.clone()),
+            Self::PortError(e) => Self::PortError(e.clone()),
+            Self::CommandWrapperError(e) => Self::CommandWrapperError(e.clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clone_keeps_io_error_context() {
+        let error = SandboxError::TcpError(TcpError::BindError(
+            3030,
+            std::io::Error::new(std::io::ErrorKind::AddrInUse, "address in use"),
+        ));
+        let cloned = error.clone();
+        assert_eq!(error.to_string(), cloned.to_string());
+
+        let SandboxError::TcpError(TcpError::BindError(port, e)) = cloned else {
+            panic!("expected bind error");
+        };
+        assert_eq!(port, 3030);
+        assert_eq!(e.kind(), std::io::ErrorKind::AddrInUse);
+    }
+
+    #[test]
+    fn test_is_unknown_account() {
+        let error = SandboxRpcError::SandboxRpcError(
+            serde_json::json!({
+                "name": "HANDLER_ERROR",
+                "cause": { "name": "UNKNOWN_ACCOUNT", "info": { "requested_account_id": "user.sandbox" } },
+            })
+            .to_string(),
+        );
+        assert!(error.is_unknown_account());
+        assert!(!SandboxRpcError::UnexpectedResponse.is_unknown_account());
+        assert!(
+            !SandboxRpcError::SandboxRpcError("UNKNOWN_ACCESS_KEY".to_owned()).is_unknown_account()
+        );
+    }
 }
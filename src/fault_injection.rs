@@ -0,0 +1,38 @@
+//! Fault injection hooks to exercise the sandbox startup retries deterministically, enabled with the
+//! `__fault_injection` feature.
+//!
+//! Meant for testing this crate and tools built on top of it, not part of the stable API.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Number of the next readiness checks that time out, set with [`crate::SandboxConfig::forced_startup_timeouts`].
+///
+/// The startup then goes through the same retry path as a port taken over by another process,
+/// failing with [`crate::error_kind::SandboxError::SandboxStartupRetriesExhausted`] once
+/// [`crate::SandboxConfig::port_transfer_retries`] attempts time out.
+///
+/// Only the sandboxes started with a config holding this counter (or a clone of it) are affected,
+/// whichever thread or runtime they are started on.
+#[derive(Debug, Clone, Default)]
+pub struct StartupTimeouts(Arc<AtomicUsize>);
+
+impl StartupTimeouts {
+    /// Make the next `count` readiness checks time out
+    pub fn new(count: usize) -> Self {
+        Self(Arc::new(AtomicUsize::new(count)))
+    }
+
+    /// Number of the forced timeouts that weren't consumed by a startup attempt yet
+    pub fn remaining(&self) -> usize {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn take(&self) -> bool {
+        self.0
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |left| {
+                left.checked_sub(1)
+            })
+            .is_ok()
+    }
+}
@@ -7,18 +7,26 @@
 //! sandboxes since `kill_on_drop` already handles cleanup. |
 //! | `generate` | off | Enables `random_account_id` and `random_key_pair` helpers |
 //! | `global_install` | off | Installs the sandbox binary under `$HOME/.near` instead of `$OUT_DIR` |
+//! | `metrics` | off | Enables `Sandbox::metrics` to fetch the prometheus metrics of the node |
+//! | `workspaces_compat` | off | Enables the [`workspaces`] module with a `near-workspaces`-like API |
 
 pub mod config;
 pub mod error_kind;
+#[cfg(any(test, feature = "__fault_injection"))]
+#[doc(hidden)]
+pub mod fault_injection;
 pub mod sandbox;
+#[cfg(feature = "workspaces_compat")]
+pub mod workspaces;
 
 mod runner;
 
 // Re-export important types for better user experience
 pub use config::{GenesisAccount, SandboxConfig};
 pub use runner::install;
-pub use sandbox::Sandbox;
 pub use sandbox::patch::FetchData;
+pub use sandbox::query::BlockReference;
+pub use sandbox::{ConnectedSandbox, Sandbox};
 
 #[cfg(feature = "generate")]
 pub use config::{random_account_id, random_key_pair};
@@ -27,3 +35,12 @@ pub use config::{random_account_id, random_key_pair};
 // Should be updated to the latest release of nearcore.
 // Currently pointing to nearcore@v2.10.7 released on March 4, 2026
 pub const DEFAULT_NEAR_SANDBOX_VERSION: &str = "2.10.7";
+
+/// Sandbox version used when none is specified explicitly.
+///
+/// Set `NEAR_SANDBOX_VERSION` env var at build time to pin a specific nearcore version
+/// without code changes, otherwise [`DEFAULT_NEAR_SANDBOX_VERSION`] is used.
+pub const NEAR_SANDBOX_VERSION: &str = match option_env!("NEAR_SANDBOX_VERSION") {
+    Some(version) => version,
+    None => DEFAULT_NEAR_SANDBOX_VERSION,
+};
@@ -1,29 +1,40 @@
 use fs4::fs_std::FileExt;
 use tokio::process::{Child, Command};
 
+use std::collections::HashMap;
 use std::fs::File;
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
+use std::sync::{Condvar, Mutex, PoisonError};
 
 use crate::error_kind::{SandboxError, TcpError};
 
 #[cfg(feature = "singleton_cleanup")]
 pub(crate) mod cleanup;
 
-// Must be an IP address as `neard` expects socket address for network address.
-const DEFAULT_RPC_HOST: &str = "127.0.0.1";
-
-pub fn rpc_socket(port: u16) -> String {
-    format!("{DEFAULT_RPC_HOST}:{port}")
-}
-
-/// Initialize a sandbox node with the provided version and home directory.
-pub fn init_with_version(home_dir: impl AsRef<Path>, version: &str) -> Result<Child, SandboxError> {
-    let bin_path = ensure_sandbox_bin_with_version(version)?;
+/// Initialize a sandbox node with the provided binary and home directory.
+///
+/// `test_seed` makes `neard` derive the node and validator keys from the seed instead of generating random ones.
+/// `env` is added to the environment of `neard` on top of the log vars.
+pub fn init(
+    home_dir: impl AsRef<Path>,
+    bin_path: &Path,
+    test_seed: Option<&str>,
+    env: &HashMap<String, String>,
+) -> Result<Child, SandboxError> {
     let home_dir = home_dir.as_ref().to_str().unwrap();
-    Command::new(&bin_path)
+    let mut command = Command::new(bin_path);
+    command
         .envs(log_vars())
-        .args(["--home", home_dir, "init", "--fast"])
+        .envs(env)
+        .args(["--home", home_dir, "init", "--fast"]);
+    if let Some(test_seed) = test_seed {
+        command.args(["--test-seed", test_seed]);
+    }
+    // Output is captured, so the reason can be reported if init fails
+    command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
         .spawn()
         .map_err(SandboxError::RuntimeError)
 }
@@ -34,29 +45,33 @@ pub fn init_with_version(home_dir: impl AsRef<Path>, version: &str) -> Result<Ch
 /// port reallocation by the OS. They are dropped just before Command::spawn()
 /// to minimize the race window where another process could claim the ports.
 ///
-/// `stderr` variable is passed to `neard` process and defaults to `Stdio::inherit` if `None` is passed
+/// `stderr` variable is passed to `neard` process and defaults to `Stdio::inherit` if `None` is passed.
+/// `env` is added to the environment of `neard` on top of the log vars.
+/// If `command_wrapper` is set, its first element is run with the rest of it followed by the `neard` path and args.
 pub fn run_neard_with_port_guards(
     home_dir: &Path,
-    version: &str,
+    bin_path: &Path,
     rpc_listener_guard: tokio::net::TcpSocket,
     net_listener_guard: tokio::net::TcpSocket,
     stderr: Option<Stdio>,
+    env: &HashMap<String, String>,
+    command_wrapper: Option<&[String]>,
 ) -> Result<Child, SandboxError> {
-    let bin_path = ensure_sandbox_bin_with_version(version)?;
-
-    let rpc_addr = rpc_socket(
-        rpc_listener_guard
-            .local_addr()
-            .map_err(TcpError::LocalAddrError)?
-            .port(),
-    );
-
-    let net_addr = rpc_socket(
-        net_listener_guard
-            .local_addr()
-            .map_err(TcpError::LocalAddrError)?
-            .port(),
-    );
+    // Must be an IP address as `neard` expects socket address for network address. The guards are
+    // bound to the loopback address neard has to listen on, either `127.0.0.1` or `::1`.
+    let rpc_addr = rpc_listener_guard
+        .local_addr()
+        .map_err(TcpError::LocalAddrError)?
+        .to_string();
+
+    let net_addr = net_listener_guard
+        .local_addr()
+        .map_err(TcpError::LocalAddrError)?
+        .to_string();
+
+    // neard runs in the home dir, so relative paths have to be resolved against the current dir first
+    let home_dir = std::path::absolute(home_dir).map_err(SandboxError::FileError)?;
+    let bin_path = std::path::absolute(bin_path).map_err(SandboxError::FileError)?;
 
     let options = &[
         "--home",
@@ -73,11 +88,23 @@ pub fn run_neard_with_port_guards(
     drop(rpc_listener_guard);
     drop(net_listener_guard);
 
+    let mut command = match command_wrapper {
+        Some([program, wrapper_args @ ..]) => {
+            let mut command = Command::new(program);
+            command.args(wrapper_args).arg(&bin_path);
+            command
+        }
+        _ => Command::new(&bin_path),
+    };
+
     // NOTE: We discard stderr of `neard`, as there might be port collisions resulting in `neard`
     // panicing that `near-sandbox` is taking care of.
-    Command::new(&bin_path)
+    // Relative paths in the neard config are resolved against the home dir instead of the CWD of the tests
+    command
+        .current_dir(&home_dir)
         .args(options)
         .envs(log_vars())
+        .envs(env)
         .stderr(stderr.unwrap_or(Stdio::inherit()))
         .kill_on_drop(true)
         .spawn()
@@ -109,7 +136,7 @@ const fn platform() -> Option<&'static str> {
 /// Installs sandbox node with the default version. This is a version that is usually stable
 /// and has landed into mainnet to reflect the latest stable features and fixes.
 pub fn install() -> Result<PathBuf, SandboxError> {
-    ensure_sandbox_bin_with_version(crate::DEFAULT_NEAR_SANDBOX_VERSION)
+    ensure_sandbox_bin_with_version(crate::NEAR_SANDBOX_VERSION, None)
 }
 
 // if the `SANDBOX_ARTIFACT_URL` env var is set, we short-circuit and use that.
@@ -136,7 +163,7 @@ fn check_for_version(version: &str) -> Result<Option<PathBuf>, SandboxError> {
 
     // version saved under {home}/.near/near-sandbox-{version}/near-sandbox
     let out_dir = download_path(version).join("near-sandbox");
-    if !out_dir.exists() {
+    if !is_usable_bin(&out_dir) {
         return Ok(None);
     }
 
@@ -146,7 +173,10 @@ fn check_for_version(version: &str) -> Result<Option<PathBuf>, SandboxError> {
 /// Install the sandbox node given the version, which is either a commit hash or tagged version
 /// number from the nearcore project. Note that commits pushed to master within the latest 12h
 /// will likely not have the binaries made available quite yet.
-fn install_with_version(version: &str) -> Result<PathBuf, SandboxError> {
+fn install_with_version(
+    version: &str,
+    agent: Option<&ureq::Agent>,
+) -> Result<PathBuf, SandboxError> {
     if let Some(bin_path) = check_for_version(version)? {
         return Ok(bin_path);
     }
@@ -157,14 +187,22 @@ fn install_with_version(version: &str) -> Result<PathBuf, SandboxError> {
         )
     })?;
 
+    let _permit = DownloadPermit::acquire(max_concurrent_downloads());
+
     // Download and extract the tar.gz archive
-    let response = ureq::get(&url)
-        .config()
-        .timeout_connect(Some(std::time::Duration::from_secs(30)))
-        .timeout_recv_response(Some(std::time::Duration::from_secs(30)))
-        .build()
-        .call()
-        .map_err(|e| SandboxError::DownloadError(e.to_string()))?;
+    let response = match agent {
+        Some(agent) => agent.get(&url),
+        None => ureq::get(&url),
+    }
+    .config()
+    .timeout_connect(Some(std::time::Duration::from_secs(30)))
+    .timeout_recv_response(Some(std::time::Duration::from_secs(30)))
+    .build()
+    .call()
+    .map_err(|e| match e {
+        ureq::Error::StatusCode(404) => SandboxError::ArtifactNotFound(version.to_owned()),
+        e => SandboxError::DownloadError(e.to_string()),
+    })?;
 
     let decoder = flate2::read::GzDecoder::new(response.into_body().into_reader());
     let mut archive = tar::Archive::new(decoder);
@@ -186,7 +224,8 @@ fn install_with_version(version: &str) -> Result<PathBuf, SandboxError> {
             // Unpack to a temporary file first, then atomically rename into place.
             // This prevents a partial file from being treated as a valid binary
             // if extraction is interrupted (e.g. network drop, disk full).
-            let tmp_dest = dest.with_extension("tmp");
+            // The name is unique per process, so concurrent installs never write the same file.
+            let tmp_dest = dest.with_extension(format!("{}.tmp", std::process::id()));
             entry
                 .unpack(&tmp_dest)
                 .map_err(|e| SandboxError::InstallError(e.to_string()))?;
@@ -209,9 +248,37 @@ fn install_with_version(version: &str) -> Result<PathBuf, SandboxError> {
     ))
 }
 
-fn installable(bin_path: &Path) -> Result<Option<std::fs::File>, SandboxError> {
+/// Checks that the binary is a non-empty file and, on Unix, is executable. An interrupted install
+/// might leave an empty or non-executable file behind, which has to be installed again.
+fn is_usable_bin(bin_path: &Path) -> bool {
+    let Ok(metadata) = std::fs::metadata(bin_path) else {
+        return false;
+    };
+    if !metadata.is_file() || metadata.len() == 0 {
+        return false;
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        metadata.permissions().mode() & 0o111 != 0
+    }
+    #[cfg(not(unix))]
+    true
+}
+
+/// Runs `install` unless the binary at `bin_path` is already usable, or always if `force` is set.
+///
+/// The check and the install are done under an exclusive lock next to the binary, so processes
+/// installing the same version at the same time don't overwrite each other, and only the first one
+/// downloads the binary. Returns the path reported by `install` if it was run.
+fn install_locked(
+    bin_path: &Path,
+    force: bool,
+    install: impl FnOnce() -> Result<PathBuf, SandboxError>,
+) -> Result<Option<PathBuf>, SandboxError> {
     // Sandbox bin already exists
-    if bin_path.exists() {
+    if !force && is_usable_bin(bin_path) {
         return Ok(None);
     }
 
@@ -222,11 +289,33 @@ fn installable(bin_path: &Path) -> Result<Option<std::fs::File>, SandboxError> {
     let lockfile = File::create(lockpath).map_err(SandboxError::FileError)?;
     lockfile.lock_exclusive().map_err(SandboxError::FileError)?;
 
-    // Check again after acquiring if no one has written to the dest path
-    if bin_path.exists() {
-        Ok(None)
-    } else {
-        Ok(Some(lockfile))
+    if force {
+        remove_bin(bin_path)?;
+    } else if is_usable_bin(bin_path) {
+        // Check again after acquiring if no one has written to the dest path
+        return Ok(None);
+    } else if bin_path.exists() {
+        tracing::warn!(
+            target: "sandbox",
+            "{} is empty or not executable, installing it again",
+            bin_path.display()
+        );
+    }
+
+    let installed = install()?;
+    FileExt::unlock(&lockfile).map_err(SandboxError::FileError)?;
+
+    Ok(Some(installed))
+}
+
+fn remove_bin(bin_path: &Path) -> Result<(), SandboxError> {
+    match std::fs::remove_file(bin_path) {
+        Ok(()) => {
+            tracing::info!(target: "sandbox", "removed cached sandbox binary {}", bin_path.display());
+            Ok(())
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(SandboxError::FileError(e)),
     }
 }
 
@@ -243,9 +332,8 @@ fn download_path(version: &str) -> PathBuf {
 
     out.push(".near");
     out.push(format!("near-sandbox-{}", normalize_name(version)));
-    if !out.exists() {
-        std::fs::create_dir_all(&out).expect("could not create download path");
-    }
+    // NOTE: `create_dir_all` succeeds if another process creates the directories at the same time
+    std::fs::create_dir_all(&out).expect("could not create download path");
 
     out
 }
@@ -269,17 +357,94 @@ fn bin_path(version: &str) -> Result<PathBuf, SandboxError> {
     Ok(buf)
 }
 
-fn ensure_sandbox_bin_with_version(version: &str) -> Result<PathBuf, SandboxError> {
-    let mut bin_path = bin_path(version)?;
-    if let Some(lockfile) = installable(&bin_path)? {
-        bin_path = install_with_version(version)?;
-        unsafe {
-            std::env::set_var("NEAR_SANDBOX_BIN_PATH", bin_path.as_os_str());
-        }
-        FileExt::unlock(&lockfile).map_err(SandboxError::FileError)?;
+/// Deletes the cached binary of the given version and downloads it again.
+/// Does nothing if the binary is provided with `NEAR_SANDBOX_BIN_PATH`, which is also set once
+/// the binary is installed, so the binary is re-downloaded at most once per process.
+pub(crate) fn reinstall(
+    version: &str,
+    agent: Option<&ureq::Agent>,
+) -> Result<PathBuf, SandboxError> {
+    install_bin(
+        version,
+        std::env::var("NEAR_SANDBOX_BIN_PATH").is_err(),
+        agent,
+    )
+}
+
+/// Number of sandbox binaries downloaded at the same time unless `NEAR_SANDBOX_MAX_CONCURRENT_DOWNLOADS` is set
+const DEFAULT_MAX_CONCURRENT_DOWNLOADS: usize = 2;
+
+/// Downloads in progress in this process and the signal that one of them finished
+static ACTIVE_DOWNLOADS: (Mutex<usize>, Condvar) = (Mutex::new(0), Condvar::new());
+
+fn max_concurrent_downloads() -> usize {
+    std::env::var("NEAR_SANDBOX_MAX_CONCURRENT_DOWNLOADS")
+        .ok()
+        .and_then(|val| val.parse().ok())
+        .filter(|&limit| limit > 0)
+        .unwrap_or(DEFAULT_MAX_CONCURRENT_DOWNLOADS)
+}
+
+/// Slot of a download, so many first-time installs of different versions don't saturate the network at once.
+///
+/// Same version installs are already serialized with [`install_locked`], this throttles the different ones.
+struct DownloadPermit;
+
+impl DownloadPermit {
+    /// Block until less than `limit` downloads are in progress in this process
+    fn acquire(limit: usize) -> Self {
+        let (active, finished) = &ACTIVE_DOWNLOADS;
+        let mut active = finished
+            .wait_while(
+                active.lock().unwrap_or_else(PoisonError::into_inner),
+                |active| *active >= limit,
+            )
+            .unwrap_or_else(PoisonError::into_inner);
+        *active += 1;
+        Self
     }
+}
+
+impl Drop for DownloadPermit {
+    fn drop(&mut self) {
+        let (active, finished) = &ACTIVE_DOWNLOADS;
+        *active.lock().unwrap_or_else(PoisonError::into_inner) -= 1;
+        finished.notify_one();
+    }
+}
 
-    Ok(bin_path)
+fn force_download() -> bool {
+    std::env::var("NEAR_SANDBOX_FORCE_DOWNLOAD").is_ok_and(|val| val == "1")
+}
+
+/// Installs the binary of the given version unless it's already installed.
+/// The binary is downloaded with `agent` if it's set, or with the default `ureq` agent otherwise.
+pub(crate) fn ensure_sandbox_bin_with_version(
+    version: &str,
+    agent: Option<&ureq::Agent>,
+) -> Result<PathBuf, SandboxError> {
+    install_bin(
+        version,
+        force_download() && std::env::var("NEAR_SANDBOX_BIN_PATH").is_err(),
+        agent,
+    )
+}
+
+fn install_bin(
+    version: &str,
+    force: bool,
+    agent: Option<&ureq::Agent>,
+) -> Result<PathBuf, SandboxError> {
+    let bin_path = bin_path(version)?;
+    match install_locked(&bin_path, force, || install_with_version(version, agent))? {
+        Some(bin_path) => {
+            unsafe {
+                std::env::set_var("NEAR_SANDBOX_BIN_PATH", bin_path.as_os_str());
+            }
+            Ok(bin_path)
+        }
+        None => Ok(bin_path),
+    }
 }
 
 fn log_vars() -> Vec<(String, String)> {
@@ -292,3 +457,95 @@ fn log_vars() -> Vec<(String, String)> {
     }
     vars
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_usable_bin() {
+        let dir = tempfile::tempdir().unwrap();
+        let bin = dir.path().join("near-sandbox");
+        assert!(!is_usable_bin(&bin));
+
+        File::create(&bin).unwrap();
+        assert!(!is_usable_bin(&bin));
+
+        std::fs::write(&bin, b"#!/bin/sh\n").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert!(!is_usable_bin(&bin));
+            std::fs::set_permissions(&bin, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        assert!(is_usable_bin(&bin));
+        assert!(!is_usable_bin(dir.path()));
+    }
+
+    #[test]
+    fn test_concurrent_install() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::{Arc, Barrier};
+
+        let dir = tempfile::tempdir().unwrap();
+        let bin = dir.path().join("near-sandbox");
+        let installs = Arc::new(AtomicUsize::new(0));
+        let barrier = Arc::new(Barrier::new(4));
+
+        // Locks are taken on separate file handles, so threads contend the same way processes do
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let (bin, installs, barrier) = (bin.clone(), installs.clone(), barrier.clone());
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    install_locked(&bin, false, || {
+                        installs.fetch_add(1, Ordering::SeqCst);
+                        std::thread::sleep(std::time::Duration::from_millis(100));
+                        std::fs::write(&bin, b"#!/bin/sh\n").unwrap();
+                        #[cfg(unix)]
+                        {
+                            use std::os::unix::fs::PermissionsExt;
+                            std::fs::set_permissions(&bin, std::fs::Permissions::from_mode(0o755))
+                                .unwrap();
+                        }
+                        Ok(bin.clone())
+                    })
+                    .unwrap()
+                })
+            })
+            .collect();
+        let installed = handles
+            .into_iter()
+            .filter_map(|handle| handle.join().unwrap())
+            .count();
+
+        assert_eq!(installs.load(Ordering::SeqCst), 1);
+        assert_eq!(installed, 1);
+        assert!(is_usable_bin(&bin));
+    }
+
+    #[test]
+    fn test_download_permit() {
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let (running, max_running) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+        let handles: Vec<_> = (0..6)
+            .map(|_| {
+                let (running, max_running) = (running.clone(), max_running.clone());
+                std::thread::spawn(move || {
+                    let _permit = DownloadPermit::acquire(2);
+                    let now_running = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_running.fetch_max(now_running, Ordering::SeqCst);
+                    std::thread::sleep(std::time::Duration::from_millis(50));
+                    running.fetch_sub(1, Ordering::SeqCst);
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert!(max_running.load(Ordering::SeqCst) <= 2);
+    }
+}
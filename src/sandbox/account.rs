@@ -1,27 +1,49 @@
 use near_account_id::AccountId;
 use near_token::NearToken;
 
-use crate::{FetchData, Sandbox, config::DEFAULT_ACCOUNT_FOR_CLONING, error_kind::SandboxRpcError};
+use crate::{
+    BlockReference, ConnectedSandbox, FetchData,
+    config::{DEFAULT_ACCOUNT_FOR_CLONING, DEFAULT_GENESIS_ACCOUNT_PUBLIC_KEY},
+    error_kind::SandboxRpcError,
+};
 
 #[derive(Clone)]
 pub struct AccountCreation<'a> {
     pub account_id: AccountId,
-    pub sandbox: &'a Sandbox,
+    pub sandbox: &'a ConnectedSandbox,
 
     pub balance: Option<NearToken>,
     pub public_key: Option<String>,
+    /// Existing account whose record is used as a base for the new account.
+    /// Defaults to the `clone` account that the sandbox genesis is created with.
+    pub template_account: Option<AccountId>,
+    /// Create the account without any access keys, see [`Self::no_access_key`]
+    pub no_access_key: bool,
+    /// Nonce of the access key, see [`Self::access_key_nonce`]
+    pub access_key_nonce: u64,
 }
 
 impl<'a> AccountCreation<'a> {
-    pub const fn new(account_id: AccountId, sandbox: &'a Sandbox) -> Self {
+    pub const fn new(account_id: AccountId, sandbox: &'a ConnectedSandbox) -> Self {
         Self {
             account_id,
             sandbox,
             balance: None,
             public_key: None,
+            template_account: None,
+            no_access_key: false,
+            access_key_nonce: 0,
         }
     }
 
+    /// Clone the base account record from `account_id` instead of the default `clone` account.
+    ///
+    /// Useful with a custom genesis that doesn't contain the default account.
+    pub fn from_template(mut self, account_id: AccountId) -> Self {
+        self.template_account = Some(account_id);
+        self
+    }
+
     pub const fn initial_balance(mut self, balance: NearToken) -> Self {
         self.balance = Some(balance);
         self
@@ -32,30 +54,53 @@ impl<'a> AccountCreation<'a> {
         self
     }
 
+    /// Don't add any access key to the account, not even the default one.
+    ///
+    /// Nobody will be able to sign transactions for such an account, which is useful to test
+    /// locked or contract-only accounts. Takes precedence over [`Self::public_key`].
+    pub const fn no_access_key(mut self) -> Self {
+        self.no_access_key = true;
+        self
+    }
+
+    /// Nonce of the last transaction signed with the access key of the account, `0` by default.
+    ///
+    /// The next transaction has to use a higher nonce, so it's useful to replay transactions signed for
+    /// another network.
+    pub const fn access_key_nonce(mut self, nonce: u64) -> Self {
+        self.access_key_nonce = nonce;
+        self
+    }
+
     pub async fn send(self) -> Result<(), SandboxRpcError> {
+        let template_account = self
+            .template_account
+            .unwrap_or_else(|| DEFAULT_ACCOUNT_FOR_CLONING.to_owned());
         let mut patch = self
             .sandbox
             .patch_state(self.account_id.clone())
             .fetch_from_account(
-                &DEFAULT_ACCOUNT_FOR_CLONING.to_owned(),
+                &template_account,
                 &self.sandbox.rpc_addr,
                 FetchData::NONE.account(),
             )
-            .await?;
+            .await
+            .map_err(|e| match e {
+                e if e.is_unknown_account() => SandboxRpcError::SandboxRpcError(format!(
+                    "account `{template_account}` to create the account from doesn't exist, \
+                    pick an existing one with `from_template`: {e}"
+                )),
+                e => e,
+            })?;
 
         if let Some(balance) = self.balance {
             patch = patch.initial_balance(balance);
         }
-        if let Some(public_key) = self.public_key {
-            patch = patch.access_key(
-                public_key,
-                serde_json::json!({
-                    "nonce": 0,
-                    "permission": "FullAccess"
-                }),
-            );
-        } else {
-            patch = patch.with_default_access_key();
+        if !self.no_access_key {
+            let public_key = self
+                .public_key
+                .unwrap_or_else(|| DEFAULT_GENESIS_ACCOUNT_PUBLIC_KEY.to_owned());
+            patch = patch.full_access_key(public_key, self.access_key_nonce);
         }
         patch.send().await?;
 
@@ -66,23 +111,37 @@ impl<'a> AccountCreation<'a> {
 #[derive(Clone)]
 pub struct AccountImport<'a> {
     pub account_id: AccountId,
-    pub sandbox: &'a Sandbox,
+    pub sandbox: &'a ConnectedSandbox,
     pub from_rpc: String,
 
     pub fetch_data: FetchData,
+    pub fetch_block: BlockReference,
+    pub fetch_headers: Vec<(String, String)>,
     pub initial_balance: Option<NearToken>,
     pub public_key: Option<String>,
+    /// Account id the fetched data is stored under in the sandbox. Defaults to [`Self::account_id`]
+    pub destination_account: Option<AccountId>,
+    /// Nonce of the added access key, see [`Self::access_key_nonce`]
+    pub access_key_nonce: Option<u64>,
 }
 
 impl<'a> AccountImport<'a> {
-    pub const fn new(account_id: AccountId, from_rpc: String, sandbox: &'a Sandbox) -> Self {
+    pub const fn new(
+        account_id: AccountId,
+        from_rpc: String,
+        sandbox: &'a ConnectedSandbox,
+    ) -> Self {
         Self {
             account_id,
             sandbox,
             from_rpc,
             fetch_data: FetchData::NONE.account().code(),
+            fetch_block: BlockReference::Optimistic,
+            fetch_headers: Vec::new(),
             initial_balance: None,
             public_key: None,
+            destination_account: None,
+            access_key_nonce: None,
         }
     }
 
@@ -96,6 +155,36 @@ impl<'a> AccountImport<'a> {
         self
     }
 
+    /// Import the account as of the given block. See [`crate::sandbox::patch::PatchState::fetch_at`]
+    pub fn fetch_at(mut self, block: BlockReference) -> Self {
+        self.fetch_block = block;
+        self
+    }
+
+    /// Attach a header (e.g. an API key) to the requests to `from_rpc`. See [`crate::sandbox::patch::PatchState::fetch_header`]
+    pub fn fetch_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.fetch_headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Store the imported account under `account_id` in the sandbox instead of the original id.
+    ///
+    /// Useful to avoid collisions with existing sandbox accounts or to test migrations
+    /// against a copy of a real contract.
+    pub fn as_account(mut self, account_id: AccountId) -> Self {
+        self.destination_account = Some(account_id);
+        self
+    }
+
+    /// Nonce of the last transaction signed with the added access key.
+    ///
+    /// If the key is imported with [`Self::with_access_keys`], it keeps the nonce of the source network
+    /// unless it's overridden here. Otherwise the nonce is `0`.
+    pub const fn access_key_nonce(mut self, nonce: u64) -> Self {
+        self.access_key_nonce = Some(nonce);
+        self
+    }
+
     pub const fn initial_balance(mut self, balance: NearToken) -> Self {
         self.initial_balance = Some(balance);
         self
@@ -107,22 +196,22 @@ impl<'a> AccountImport<'a> {
     }
 
     pub async fn send(self) -> Result<(), SandboxRpcError> {
-        let mut patch = self
-            .sandbox
-            .patch_state(self.account_id.clone())
-            .fetch_from(self.from_rpc, self.fetch_data)
+        let destination_account = self
+            .destination_account
+            .unwrap_or_else(|| self.account_id.clone());
+        let mut patch = self.sandbox.patch_state(destination_account);
+        patch.fetch_headers = self.fetch_headers;
+        let mut patch = patch
+            .fetch_at(self.fetch_block)
+            .fetch_from_account(&self.account_id, self.from_rpc, self.fetch_data)
             .await?;
 
-        if let Some(public_key) = self.public_key {
-            patch = patch.access_key(
-                public_key,
-                serde_json::json!({
-                    "nonce": 0,
-                    "permission": "FullAccess"
-                }),
-            );
-        } else {
-            patch = patch.with_default_access_key();
+        let public_key = self
+            .public_key
+            .unwrap_or_else(|| DEFAULT_GENESIS_ACCOUNT_PUBLIC_KEY.to_owned());
+        // The fetched key carries the nonce of the source, so replayed transactions pass the nonce checks
+        if self.access_key_nonce.is_some() || !patch.has_access_key(&public_key) {
+            patch = patch.full_access_key(public_key, self.access_key_nonce.unwrap_or(0));
         }
 
         if let Some(balance) = self.initial_balance {
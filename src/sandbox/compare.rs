@@ -0,0 +1,192 @@
+//! Helpers for checking that the account state was replicated into the sandbox.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use near_account_id::AccountId;
+use serde_json::Value;
+
+use crate::ConnectedSandbox;
+use crate::error_kind::SandboxRpcError;
+use crate::sandbox::query::{BlockReference, QueryRequest};
+
+/// Differences between the account state in the sandbox and on the other RPC
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountDiff {
+    pub account_id: AccountId,
+    /// Fields of the account view that differ, as `(field, sandbox value, other value)`.
+    /// The contract code is compared through the `code_hash` field.
+    pub account: Vec<(String, Value, Value)>,
+    /// Base64 encoded storage keys that have different values or exist only on one side
+    pub storage_keys: Vec<String>,
+}
+
+impl AccountDiff {
+    pub fn is_empty(&self) -> bool {
+        self.account.is_empty() && self.storage_keys.is_empty()
+    }
+}
+
+impl fmt::Display for AccountDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "account {} differs:", self.account_id)?;
+        for (field, sandbox, other) in &self.account {
+            write!(
+                f,
+                " `{field}` is {sandbox} in sandbox and {other} on the other RPC;"
+            )?;
+        }
+        if !self.storage_keys.is_empty() {
+            write!(f, " storage keys {:?} differ", self.storage_keys)?;
+        }
+        Ok(())
+    }
+}
+
+impl ConnectedSandbox {
+    /// Check that the account, its code and storage in the sandbox match the ones on `other_rpc`.
+    ///
+    /// Returns [`SandboxRpcError::AccountMismatch`] with the [`AccountDiff`] if they don't.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use near_sandbox::*;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let sandbox = Sandbox::start_sandbox().await?;
+    /// let account_id = "user.testnet".parse()?;
+    /// let testnet = "https://rpc.testnet.near.org";
+    /// sandbox
+    ///     .import_account(testnet, account_id.clone())
+    ///     .with_storage()
+    ///     .send()
+    ///     .await?;
+    /// sandbox.assert_account_matches(&account_id, testnet).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn assert_account_matches(
+        &self,
+        account_id: &AccountId,
+        other_rpc: impl AsRef<str>,
+    ) -> Result<(), SandboxRpcError> {
+        let other_rpc = other_rpc.as_ref();
+        let mut diff = AccountDiff {
+            account_id: account_id.clone(),
+            account: vec![],
+            storage_keys: vec![],
+        };
+
+        let view_account = QueryRequest::ViewAccount {
+            account_id: account_id.clone(),
+        };
+        let account = self.view(&self.rpc_addr, &view_account).await?;
+        let other_account = self.view(other_rpc, &view_account).await?;
+        let fields = account_fields(&account);
+        let other_fields = account_fields(&other_account);
+        for field in fields
+            .keys()
+            .chain(other_fields.keys())
+            .collect::<BTreeSet<_>>()
+        {
+            let value = fields.get(field).cloned().unwrap_or(Value::Null);
+            let other_value = other_fields.get(field).cloned().unwrap_or(Value::Null);
+            if value != other_value {
+                diff.account.push((field.clone(), value, other_value));
+            }
+        }
+
+        let view_state = QueryRequest::ViewState {
+            account_id: account_id.clone(),
+            prefix: Vec::new(),
+            include_proof: false,
+        };
+        let storage = storage_entries(&self.view(&self.rpc_addr, &view_state).await?);
+        let other_storage = storage_entries(&self.view(other_rpc, &view_state).await?);
+        diff.storage_keys = storage
+            .keys()
+            .chain(other_storage.keys())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .filter(|key| storage.get(*key) != other_storage.get(*key))
+            .cloned()
+            .collect();
+
+        if diff.is_empty() {
+            Ok(())
+        } else {
+            Err(SandboxRpcError::AccountMismatch(Box::new(diff)))
+        }
+    }
+
+    async fn view(&self, rpc: &str, request: &QueryRequest) -> Result<Value, SandboxRpcError> {
+        self.query_rpc(rpc, &[], request, &BlockReference::Optimistic)
+            .await
+    }
+}
+
+/// Account view fields without the block the view was taken at
+fn account_fields(account: &Value) -> BTreeMap<String, Value> {
+    account
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter(|(field, _)| !matches!(field.as_str(), "block_hash" | "block_height"))
+        .map(|(field, value)| (field.clone(), value.clone()))
+        .collect()
+}
+
+fn storage_entries(state: &Value) -> BTreeMap<String, String> {
+    state
+        .get("values")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            Some((
+                entry.get("key")?.as_str()?.to_owned(),
+                entry.get("value")?.as_str()?.to_owned(),
+            ))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use near_token::NearToken;
+
+    use crate::Sandbox;
+    use crate::error_kind::SandboxRpcError;
+
+    #[tokio::test]
+    async fn test_assert_account_matches() {
+        let sandbox = Sandbox::start_sandbox().await.unwrap();
+        let other = Sandbox::start_sandbox().await.unwrap();
+        let account_id: near_account_id::AccountId = "bob.sandbox".parse().unwrap();
+
+        for (sandbox, balance) in [(&sandbox, 1), (&other, 2)] {
+            sandbox
+                .create_account(account_id.clone())
+                .initial_balance(NearToken::from_near(balance))
+                .send()
+                .await
+                .unwrap();
+        }
+
+        sandbox
+            .assert_account_matches(&account_id, &sandbox.rpc_addr)
+            .await
+            .unwrap();
+
+        let Err(SandboxRpcError::AccountMismatch(diff)) = sandbox
+            .assert_account_matches(&account_id, &other.rpc_addr)
+            .await
+        else {
+            panic!("expected account mismatch");
+        };
+        assert_eq!(diff.account.len(), 1);
+        assert_eq!(diff.account[0].0, "amount");
+        assert!(diff.storage_keys.is_empty());
+    }
+}
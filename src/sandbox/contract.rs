@@ -0,0 +1,330 @@
+//! Builder for the common contract setup: create the account, deploy the code and call the init method.
+
+use std::path::PathBuf;
+
+use near_account_id::AccountId;
+use near_token::NearToken;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use crate::ConnectedSandbox;
+use crate::config::DEFAULT_GENESIS_ACCOUNT_PRIVATE_KEY;
+use crate::error_kind::SandboxRpcError;
+use crate::sandbox::signer::Action;
+use crate::sandbox::transaction::TxOutcome;
+
+/// Gas attached to function calls by default, same as in `near-workspaces`
+pub const DEFAULT_CALL_FN_GAS: u64 = 30_000_000_000_000;
+
+#[derive(Debug, Clone)]
+enum ContractCode {
+    Wasm(Vec<u8>),
+    File(PathBuf),
+}
+
+/// Contract deployment, created with [`ConnectedSandbox::deploy_contract`] and run with [`Self::send`]
+#[derive(Debug, Clone)]
+pub struct ContractDeployment<'a> {
+    pub account_id: AccountId,
+    pub sandbox: &'a ConnectedSandbox,
+
+    /// Balance of the account if it has to be created
+    pub balance: Option<NearToken>,
+    code: Option<ContractCode>,
+    init: Option<(String, Result<Vec<u8>, SandboxRpcError>)>,
+}
+
+impl<'a> ContractDeployment<'a> {
+    pub const fn new(account_id: AccountId, sandbox: &'a ConnectedSandbox) -> Self {
+        Self {
+            account_id,
+            sandbox,
+            balance: None,
+            code: None,
+            init: None,
+        }
+    }
+
+    pub fn code(mut self, wasm: impl Into<Vec<u8>>) -> Self {
+        self.code = Some(ContractCode::Wasm(wasm.into()));
+        self
+    }
+
+    /// Read the code from the file when the deployment is sent
+    pub fn code_from_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.code = Some(ContractCode::File(path.into()));
+        self
+    }
+
+    /// Call `method` with JSON `args` once the code is deployed.
+    ///
+    /// Args failing to serialize are reported by [`Self::send`], before anything is deployed.
+    pub fn init(mut self, method: impl Into<String>, args: impl Serialize) -> Self {
+        self.init = Some((method.into(), serialize_args(args)));
+        self
+    }
+
+    pub const fn initial_balance(mut self, balance: NearToken) -> Self {
+        self.balance = Some(balance);
+        self
+    }
+
+    /// Create the account if it doesn't exist, deploy the code and call the init method.
+    ///
+    /// The account is created with the default genesis key, and an existing account has to have it as
+    /// a full access key, as the deployment and the init call are signed with
+    /// [`DEFAULT_GENESIS_ACCOUNT_PRIVATE_KEY`]. A failed init call is returned as an error.
+    pub async fn send(self) -> Result<Contract, SandboxRpcError> {
+        let wasm = match self.code {
+            Some(ContractCode::Wasm(wasm)) => wasm,
+            Some(ContractCode::File(path)) => {
+                std::fs::read(&path).map_err(|e| SandboxRpcError::ContractCodeRead(path, e))?
+            }
+            None => {
+                return Err(SandboxRpcError::MissingContractCode(
+                    self.account_id.to_string(),
+                ));
+            }
+        };
+        let init = match self.init {
+            Some((method, args)) => Some((method, args?)),
+            None => None,
+        };
+
+        if !self.sandbox.account_exists(&self.account_id).await? {
+            let mut creation = self.sandbox.create_account(self.account_id.clone());
+            if let Some(balance) = self.balance {
+                creation = creation.initial_balance(balance);
+            }
+            creation.send().await?;
+        }
+
+        let contract = Contract {
+            account_id: self.account_id,
+            sandbox: self.sandbox.clone(),
+        };
+        contract
+            .sign_and_send(Action::DeployContract(wasm))
+            .await?
+            .into_result()?;
+
+        if let Some((method, args)) = init {
+            contract
+                .call(method)
+                .args(args)
+                .send()
+                .await?
+                .into_result()?;
+        }
+
+        Ok(contract)
+    }
+}
+
+/// Contract deployed with [`ConnectedSandbox::deploy_contract`].
+///
+/// The calls are signed by the contract account with [`DEFAULT_GENESIS_ACCOUNT_PRIVATE_KEY`].
+#[derive(Debug, Clone)]
+pub struct Contract {
+    pub account_id: AccountId,
+    sandbox: ConnectedSandbox,
+}
+
+impl Contract {
+    pub const fn id(&self) -> &AccountId {
+        &self.account_id
+    }
+
+    /// Call a method of this contract signed by the contract account itself
+    pub fn call(&self, method: impl Into<String>) -> ContractCall<'_> {
+        ContractCall {
+            contract: self,
+            method: method.into(),
+            gas: DEFAULT_CALL_FN_GAS,
+            deposit: NearToken::from_yoctonear(0),
+            args: Ok(vec![]),
+        }
+    }
+
+    /// Call a view method of this contract with JSON `args`, see [`ConnectedSandbox::view_call_json`]
+    pub async fn view<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        args: impl Serialize,
+    ) -> Result<T, SandboxRpcError> {
+        self.sandbox
+            .view_call_json(&self.account_id, method, args)
+            .await
+    }
+
+    async fn sign_and_send(&self, action: Action) -> Result<TxOutcome, SandboxRpcError> {
+        self.sandbox
+            .sign_and_send(
+                &self.account_id,
+                DEFAULT_GENESIS_ACCOUNT_PRIVATE_KEY,
+                &self.account_id,
+                &[action],
+            )
+            .await
+    }
+}
+
+/// Function call of a [`Contract`], sent with [`Self::send`]
+#[derive(Debug, Clone)]
+pub struct ContractCall<'a> {
+    pub contract: &'a Contract,
+    pub method: String,
+    /// Gas to attach, defaults to [`DEFAULT_CALL_FN_GAS`]
+    pub gas: u64,
+    pub deposit: NearToken,
+    args: Result<Vec<u8>, SandboxRpcError>,
+}
+
+impl ContractCall<'_> {
+    pub fn args(mut self, args: Vec<u8>) -> Self {
+        self.args = Ok(args);
+        self
+    }
+
+    /// Args failing to serialize are reported by [`Self::send`]
+    pub fn args_json(mut self, args: impl Serialize) -> Self {
+        self.args = serialize_args(args);
+        self
+    }
+
+    pub const fn gas(mut self, gas: u64) -> Self {
+        self.gas = gas;
+        self
+    }
+
+    pub const fn deposit(mut self, deposit: NearToken) -> Self {
+        self.deposit = deposit;
+        self
+    }
+
+    /// Sign the transaction and wait until it's executed.
+    ///
+    /// A failed execution isn't an error, check it with [`TxOutcome::into_result`].
+    pub async fn send(self) -> Result<TxOutcome, SandboxRpcError> {
+        self.contract
+            .sign_and_send(Action::FunctionCall {
+                method: self.method,
+                args: self.args?,
+                gas: self.gas,
+                deposit: self.deposit.as_yoctonear(),
+            })
+            .await
+    }
+}
+
+pub(crate) fn serialize_args(args: impl Serialize) -> Result<Vec<u8>, SandboxRpcError> {
+    serde_json::to_vec(&args).map_err(|e| SandboxRpcError::ArgsSerializeError(e.to_string()))
+}
+
+impl ConnectedSandbox {
+    /// Deploy a contract to `account_id` in one go, creating the account and calling the init method if needed.
+    ///
+    /// Returns the contract, which can sign the follow-up calls as its own account.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use near_sandbox::*;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let sandbox = Sandbox::start_sandbox().await?;
+    /// let contract = sandbox
+    ///     .deploy_contract("counter.sandbox".parse()?)
+    ///     .code_from_file("res/counter.wasm")
+    ///     .init("new", serde_json::json!({ "start": 10 }))
+    ///     .send()
+    ///     .await?;
+    ///
+    /// contract.call("increment").send().await?.into_result()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub const fn deploy_contract(&self, account_id: AccountId) -> ContractDeployment<'_> {
+        ContractDeployment::new(account_id, self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Sandbox;
+    use crate::config::DEFAULT_GENESIS_ACCOUNT_PUBLIC_KEY;
+    use crate::error_kind::SandboxRpcError;
+    use crate::sandbox::tests::NOOP_CONTRACT;
+
+    #[tokio::test]
+    async fn test_deploy_contract() {
+        let sandbox = Sandbox::start_sandbox().await.unwrap();
+        let account_id: near_account_id::AccountId = "contract.sandbox".parse().unwrap();
+
+        let contract = sandbox
+            .deploy_contract(account_id.clone())
+            .code(NOOP_CONTRACT)
+            .init("new", serde_json::json!({}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(contract.id(), &account_id);
+        assert_eq!(
+            sandbox.contract_code(&account_id).await.unwrap(),
+            NOOP_CONTRACT
+        );
+        // The deployment and the init call were signed with the key of the created account
+        let access_key = sandbox
+            .access_key(&account_id, DEFAULT_GENESIS_ACCOUNT_PUBLIC_KEY)
+            .await
+            .unwrap();
+        assert_eq!(access_key.nonce, 2);
+
+        assert!(contract.call("new").send().await.unwrap().is_success());
+        let missing = contract.call("missing").send().await.unwrap();
+        assert!(missing.into_result().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_deploy_contract_init_args_error() {
+        let sandbox = Sandbox::start_sandbox().await.unwrap();
+        let account_id: near_account_id::AccountId = "contract.sandbox".parse().unwrap();
+
+        // JSON object keys have to be strings
+        let args = std::collections::HashMap::from([((1, 2), 3)]);
+        let result = sandbox
+            .deploy_contract(account_id.clone())
+            .code(NOOP_CONTRACT)
+            .init("new", args)
+            .send()
+            .await;
+        assert!(matches!(
+            result,
+            Err(SandboxRpcError::ArgsSerializeError(_))
+        ));
+        assert!(!sandbox.account_exists(&account_id).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_deploy_contract_requires_code() {
+        let sandbox = Sandbox::start_sandbox().await.unwrap();
+        let account_id: near_account_id::AccountId = "contract.sandbox".parse().unwrap();
+
+        let result = sandbox
+            .deploy_contract(account_id.clone())
+            .code_from_file("/nonexistent/contract.wasm")
+            .send()
+            .await;
+        assert!(matches!(
+            result,
+            Err(SandboxRpcError::ContractCodeRead(_, _))
+        ));
+        assert!(!sandbox.account_exists(&account_id).await.unwrap());
+
+        let result = sandbox.deploy_contract(account_id.clone()).send().await;
+        assert!(matches!(
+            result,
+            Err(SandboxRpcError::MissingContractCode(_))
+        ));
+    }
+}
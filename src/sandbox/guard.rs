@@ -0,0 +1,98 @@
+//! Explicit RAII handle for the sandbox process, see [`Sandbox::drop_guard`].
+
+use std::sync::{Arc, Mutex, PoisonError};
+
+use tracing::info;
+
+use crate::Sandbox;
+use crate::sandbox::SandboxProcess;
+
+/// Stops the sandbox process when dropped, even if the [`Sandbox`] itself is still alive.
+///
+/// Lets custom harnesses tie the lifetime of the process to a specific scope while the sandbox handle is
+/// moved around. The guard follows the process relaunched by [`Sandbox::reset_state`], and dropping the
+/// [`Sandbox`] still stops the process as usual. Use [`Self::disarm`] to drop the guard without stopping it.
+#[must_use = "the sandbox is stopped as soon as the guard is dropped"]
+pub struct SandboxGuard {
+    process: Arc<Mutex<SandboxProcess>>,
+    armed: bool,
+}
+
+impl SandboxGuard {
+    /// Drop the guard without stopping the sandbox
+    pub fn disarm(mut self) {
+        self.armed = false;
+    }
+}
+
+impl Drop for SandboxGuard {
+    fn drop(&mut self) {
+        if !self.armed {
+            return;
+        }
+
+        let process = &mut self
+            .process
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .child;
+        info!(
+            target: "sandbox",
+            "Stopping sandbox on guard drop: pid={:?}",
+            process.id()
+        );
+
+        if let Err(e) = process.start_kill() {
+            tracing::debug!(target: "sandbox", "Kill returned error (may already be dead): {}", e);
+        }
+
+        let _ = process.try_wait();
+    }
+}
+
+impl Sandbox {
+    /// Guard that stops the sandbox process once it goes out of scope, see [`SandboxGuard`].
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use near_sandbox::*;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let sandbox = Sandbox::start_sandbox().await?;
+    /// {
+    ///     let _guard = sandbox.drop_guard();
+    ///     sandbox.fast_forward(10).await?;
+    /// }
+    /// // The process is stopped here, while `sandbox` can still be inspected, e.g. its home directory
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn drop_guard(&self) -> SandboxGuard {
+        SandboxGuard {
+            process: self.process.clone(),
+            armed: true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::Sandbox;
+    use crate::sandbox::health::Health;
+
+    #[tokio::test]
+    async fn test_drop_guard() {
+        let sandbox = Sandbox::start_sandbox().await.unwrap();
+
+        sandbox.drop_guard().disarm();
+        assert_eq!(sandbox.health().await, Health::Ready);
+
+        drop(sandbox.drop_guard());
+        while !matches!(sandbox.health().await, Health::Dead(_)) {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    }
+}
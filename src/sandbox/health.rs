@@ -0,0 +1,81 @@
+//! Health of the sandbox, combining the process liveness with the RPC status.
+
+use std::process::ExitStatus;
+
+use crate::Sandbox;
+use crate::error_kind::SandboxRpcError;
+
+/// State of the sandbox returned by [`Sandbox::health`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Health {
+    /// The process is running, but the RPC isn't accepting connections yet or the node is still syncing
+    Starting,
+    /// The node is running and serves RPC requests
+    Ready,
+    /// The process is running, but the RPC fails with the given reason
+    Unhealthy(String),
+    /// The process has exited with the given status
+    Dead(ExitStatus),
+}
+
+impl Sandbox {
+    /// Check whether the sandbox process is alive and its RPC responds.
+    ///
+    /// Useful for tools supervising multiple sandboxes, e.g. to replace the dead ones in a pool.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use near_sandbox::*;
+    /// use near_sandbox::sandbox::health::Health;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let sandbox = Sandbox::start_sandbox().await?;
+    /// match sandbox.health().await {
+    ///     Health::Ready => println!("ready"),
+    ///     Health::Dead(status) => println!("sandbox exited with {status}"),
+    ///     health => println!("not ready yet: {health:?}"),
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn health(&self) -> Health {
+        match self.lock_process().child.try_wait() {
+            Ok(Some(status)) => return Health::Dead(status),
+            Ok(None) => {}
+            Err(e) => return Health::Unhealthy(format!("failed to check the process: {e}")),
+        }
+
+        match self.sync_info().await {
+            Ok(sync_info) if sync_info.syncing => Health::Starting,
+            Ok(_) => Health::Ready,
+            Err(SandboxRpcError::RequestError(e)) if is_connection_refused(&e) => Health::Starting,
+            Err(e) => Health::Unhealthy(e.to_string()),
+        }
+    }
+}
+
+fn is_connection_refused(error: &ureq::Error) -> bool {
+    match error {
+        ureq::Error::ConnectionFailed => true,
+        ureq::Error::Io(e) => e.kind() == std::io::ErrorKind::ConnectionRefused,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_health() {
+        let sandbox = Sandbox::start_sandbox().await.unwrap();
+        assert_eq!(sandbox.health().await, Health::Ready);
+
+        sandbox.lock_process().child.start_kill().unwrap();
+        while sandbox.lock_process().child.try_wait().unwrap().is_none() {
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+        assert!(matches!(sandbox.health().await, Health::Dead(_)));
+    }
+}
@@ -0,0 +1,159 @@
+//! Capture of the neard logs, enabled with [`crate::SandboxConfig::capture_logs`].
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Instant;
+
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::Child;
+
+use crate::Sandbox;
+
+/// Lines written by neard to stderr, with the time they were received at
+#[derive(Debug, Clone)]
+pub(crate) struct CapturedLogs {
+    buffer: Arc<Mutex<LogBuffer>>,
+}
+
+/// Ring of the latest lines, the oldest ones are dropped once the lines take more than `max_bytes`
+#[derive(Debug)]
+struct LogBuffer {
+    lines: VecDeque<(Instant, String)>,
+    bytes: usize,
+    max_bytes: usize,
+}
+
+impl CapturedLogs {
+    pub(crate) fn new(max_bytes: usize) -> Self {
+        Self {
+            buffer: Arc::new(Mutex::new(LogBuffer {
+                lines: VecDeque::new(),
+                bytes: 0,
+                max_bytes,
+            })),
+        }
+    }
+
+    /// Start collecting the stderr of `child`, which has to be spawned with piped stderr
+    pub(crate) fn capture(&self, child: &mut Child) {
+        let Some(stderr) = child.stderr.take() else {
+            return;
+        };
+
+        let captured = self.clone();
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stderr).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                captured.push(Instant::now(), line);
+            }
+        });
+    }
+
+    fn push(&self, received_at: Instant, line: String) {
+        let mut buffer = self.buffer.lock().unwrap_or_else(PoisonError::into_inner);
+        buffer.bytes += line.len();
+        buffer.lines.push_back((received_at, line));
+
+        // The latest line is kept even if it doesn't fit on its own
+        while buffer.bytes > buffer.max_bytes && buffer.lines.len() > 1 {
+            if let Some((_, dropped)) = buffer.lines.pop_front() {
+                buffer.bytes -= dropped.len();
+            }
+        }
+    }
+
+    fn since(&self, since: Option<Instant>) -> String {
+        self.buffer
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .lines
+            .iter()
+            .filter(|(received_at, _)| since.is_none_or(|since| *received_at >= since))
+            .map(|(_, line)| line.as_str())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+impl Sandbox {
+    /// All the neard logs captured so far.
+    ///
+    /// Empty unless the sandbox was started with [`crate::SandboxConfig::capture_logs`].
+    /// Only the latest [`crate::SandboxConfig::log_buffer_bytes`] of the logs are kept.
+    pub fn logs(&self) -> String {
+        self.logs
+            .as_ref()
+            .map(|logs| logs.since(None))
+            .unwrap_or_default()
+    }
+
+    /// The neard logs received at or after `since`.
+    ///
+    /// Useful to print only the logs of the failed operation instead of the whole run.
+    /// Empty unless the sandbox was started with [`crate::SandboxConfig::capture_logs`].
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use std::time::Instant;
+    /// use near_sandbox::*;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let sandbox = Sandbox::start_sandbox_with_config(SandboxConfig {
+    ///     capture_logs: true,
+    ///     ..Default::default()
+    /// })
+    /// .await?;
+    ///
+    /// let started_at = Instant::now();
+    /// if let Err(e) = sandbox.fast_forward(100).await {
+    ///     eprintln!("fast_forward failed: {e}\n{}", sandbox.logs_since(started_at));
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn logs_since(&self, since: Instant) -> String {
+        self.logs
+            .as_ref()
+            .map(|logs| logs.since(Some(since)))
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_logs_since() {
+        let logs = CapturedLogs::new(1024);
+        let start = Instant::now();
+        logs.push(start, "startup".to_owned());
+        logs.push(start + Duration::from_secs(1), "first".to_owned());
+        logs.push(start + Duration::from_secs(2), "second".to_owned());
+
+        assert_eq!(logs.since(None), "startup\nfirst\nsecond");
+        assert_eq!(
+            logs.since(Some(start + Duration::from_secs(1))),
+            "first\nsecond"
+        );
+        assert_eq!(logs.since(Some(start + Duration::from_secs(3))), "");
+    }
+
+    #[test]
+    fn test_logs_buffer_limit() {
+        let logs = CapturedLogs::new(10);
+        let start = Instant::now();
+        logs.push(start, "12345".to_owned());
+        logs.push(start, "67890".to_owned());
+        assert_eq!(logs.since(None), "12345\n67890");
+
+        logs.push(start, "abc".to_owned());
+        assert_eq!(logs.since(None), "67890\nabc");
+
+        logs.push(start, "a line longer than the limit".to_owned());
+        assert_eq!(logs.since(None), "a line longer than the limit");
+    }
+}
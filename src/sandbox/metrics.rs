@@ -0,0 +1,65 @@
+//! Access to the prometheus metrics exposed by neard.
+
+use crate::ConnectedSandbox;
+use crate::error_kind::SandboxRpcError;
+
+impl ConnectedSandbox {
+    /// Address of the prometheus metrics endpoint. neard serves it on the RPC port.
+    pub fn metrics_addr(&self) -> String {
+        format!("{}/metrics", self.rpc_addr)
+    }
+
+    /// Fetch the raw prometheus metrics in the text exposition format.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use near_sandbox::*;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let sandbox = Sandbox::start_sandbox().await?;
+    /// sandbox.fast_forward(100).await?;
+    /// let metrics = sandbox.metrics().await?;
+    /// for line in metrics.lines().filter(|line| line.starts_with("near_block_height_head")) {
+    ///     println!("{line}");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn metrics(&self) -> Result<String, SandboxRpcError> {
+        let url = self.metrics_addr();
+        let timeout = self.rpc_timeout();
+        let agent = self.rpc_client().clone();
+
+        tokio::task::spawn_blocking(move || {
+            agent
+                .get(&url)
+                .config()
+                .timeout_global(Some(timeout))
+                .build()
+                .call()?
+                .into_body()
+                .read_to_string()
+        })
+        .await
+        .map_err(|e| {
+            let io_err = std::io::Error::other(e.to_string());
+            ureq::Error::from(io_err)
+        })?
+        .map_err(SandboxRpcError::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Sandbox;
+
+    #[tokio::test]
+    async fn test_metrics() {
+        let sandbox = Sandbox::start_sandbox().await.unwrap();
+        sandbox.fast_forward(5).await.unwrap();
+
+        let metrics = sandbox.metrics().await.unwrap();
+        assert!(metrics.lines().any(|line| line.starts_with("near_")));
+    }
+}
@@ -1,33 +1,206 @@
 use fs4::fs_std::FileExt;
 use near_account_id::AccountId;
-use std::net::SocketAddrV4;
+use near_token::NearToken;
+use std::collections::{BTreeSet, HashMap};
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+use std::ops::Deref;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
-use std::time::Duration;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
 use std::{fs::File, net::Ipv4Addr};
 use tempfile::TempDir;
 use tokio::net::TcpSocket;
 use tokio::process::Child;
 use tracing::{error, info, warn};
 
-use crate::config::{self, SandboxConfig};
+use crate::config::{self, GenesisAccount, SandboxConfig};
 use crate::error_kind::{SandboxError, SandboxRpcError, TcpError};
-use crate::runner::{init_with_version, run_neard_with_port_guards};
+use crate::runner::{ensure_sandbox_bin_with_version, init, run_neard_with_port_guards};
 use crate::sandbox::account::{AccountCreation, AccountImport};
-use crate::sandbox::patch::PatchState;
+use crate::sandbox::logs::CapturedLogs;
+use crate::sandbox::patch::{FetchData, PatchState, StateRecord};
+use crate::sandbox::query::{BlockReference, SyncInfo};
+use crate::sandbox::signer::{Action, NonceCache, parse_secret_key};
 
 #[cfg(feature = "singleton_cleanup")]
 use crate::runner::cleanup::CleanupGuard;
 
 pub mod account;
+pub mod compare;
+pub mod contract;
+pub mod guard;
+pub mod health;
+mod logs;
+#[cfg(feature = "metrics")]
+pub mod metrics;
 pub mod patch;
+pub mod query;
+pub mod read_only;
+pub(crate) mod signer;
+pub mod transaction;
+
+/// Interval between the height checks of [`ConnectedSandbox::fast_forward`].
+///
+/// Large jumps take a while, so they are polled less often to not slow the node down with
+/// `status` requests: 0.5ms per block, between 100ms and 1s.
+const fn fast_forward_poll_interval(blocks: u64) -> Duration {
+    const MIN_MILLIS: u64 = 100;
+    const MAX_MILLIS: u64 = 1000;
+
+    let millis = blocks / 2;
+    Duration::from_millis(if millis < MIN_MILLIS {
+        MIN_MILLIS
+    } else if millis > MAX_MILLIS {
+        MAX_MILLIS
+    } else {
+        millis
+    })
+}
+
+/// Time allowed per block on top of the RPC timeout by [`ConnectedSandbox::produce_blocks`].
+///
+/// Blocks are produced roughly every second, give it some headroom on slow CI machines.
+const PRODUCE_BLOCK_TIMEOUT: Duration = Duration::from_secs(2);
+/// Time allowed per block on top of the RPC timeout by [`ConnectedSandbox::fast_forward`].
+///
+/// The skipped blocks are produced much faster than the regular ones, but large jumps still take a while.
+const FAST_FORWARD_BLOCK_TIMEOUT: Duration = Duration::from_millis(5);
+
+/// Deadline of the wait for `blocks` new blocks, so the large waits don't fail with a short RPC timeout
+fn blocks_timeout(rpc_timeout: Duration, blocks: u64, per_block: Duration) -> Duration {
+    u32::try_from(blocks)
+        .ok()
+        .and_then(|blocks| per_block.checked_mul(blocks))
+        .and_then(|wait| rpc_timeout.checked_add(wait))
+        .unwrap_or(Duration::MAX)
+}
+
+/// NEAR-implicit (64 hex chars) and ETH-implicit (`0x` and 40 hex chars) accounts are created by a transfer alone
+fn is_implicit_account(account_id: &AccountId) -> bool {
+    let id = account_id.as_str();
+    let is_hex = |s: &str| {
+        s.bytes()
+            .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+    };
+    (id.len() == 64 && is_hex(id))
+        || id
+            .strip_prefix("0x")
+            .is_some_and(|hex| hex.len() == 40 && is_hex(hex))
+}
+
+/// Delay before the first retry of a request failed with a transient error, doubled on every next retry
+const RPC_RETRY_BACKOFF: Duration = Duration::from_millis(200);
+
+/// Whether the request may succeed if sent again, see [`SandboxConfig::rpc_retries`]
+fn is_transient_rpc_error(error: &SandboxRpcError) -> bool {
+    let SandboxRpcError::RequestError(error) = error else {
+        return false;
+    };
+    match error.as_ref() {
+        ureq::Error::ConnectionFailed => true,
+        ureq::Error::Io(e) => matches!(
+            e.kind(),
+            std::io::ErrorKind::ConnectionRefused
+                | std::io::ErrorKind::ConnectionReset
+                | std::io::ErrorKind::ConnectionAborted
+                | std::io::ErrorKind::BrokenPipe
+                | std::io::ErrorKind::UnexpectedEof
+        ),
+        // nearcore responds with 408 to the requests timed out on its side
+        ureq::Error::StatusCode(status) => *status == 408 || (500..600).contains(status),
+        _ => false,
+    }
+}
+
+/// Whether the JSON RPC error is a timeout of the node rather than an error of the request
+fn is_rpc_timeout_error(error: &serde_json::Value) -> bool {
+    error["code"] == -32000 && error["cause"]["name"] == "TIMEOUT_ERROR"
+}
+
+/// Poll the `status` endpoint of the RPC every 500ms until it responds or `deadline` passes.
+///
+/// The requests time out with `rpc_timeout`, cut to the time left, so a hanging request doesn't overrun the deadline.
+async fn wait_for_status(
+    rpc: &str,
+    rpc_timeout: Duration,
+    agent: &ureq::Agent,
+    deadline: Instant,
+) -> Result<(), SandboxError> {
+    let mut interval = tokio::time::interval(Duration::from_millis(500));
+    let status_url = format!("{rpc}/status");
+    loop {
+        interval.tick().await;
+        let url = status_url.clone();
+        let agent = agent.clone();
+        let timeout = rpc_timeout.min(deadline.saturating_duration_since(Instant::now()));
+        let response = tokio::task::spawn_blocking(move || {
+            agent
+                .get(&url)
+                .config()
+                .timeout_global(Some(timeout))
+                .build()
+                .call()
+        })
+        .await
+        .map_err(|e| SandboxError::RuntimeError(std::io::Error::other(e)))?;
+        if response.is_ok() {
+            return Ok(());
+        }
+        if Instant::now() >= deadline {
+            return Err(SandboxError::TimeoutError);
+        }
+    }
+}
+
+/// Version of the CI build of the nearcore commit, `{branch}/{hash}` with the branch defaulting to `master`
+fn commit_version(commit: &str) -> Result<String, SandboxError> {
+    let (branch, hash) = commit.rsplit_once('/').unwrap_or(("master", commit));
+    if branch.is_empty() || hash.len() != 40 || !hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(SandboxError::BinaryError(format!(
+            "`{commit}` is not a full 40 character commit hash"
+        )));
+    }
+
+    Ok(format!("{branch}/{}", hash.to_ascii_lowercase()))
+}
 
 /// Request an unused port, bound by TcpListener from the OS.
 async fn pick_unused_port_guard() -> Result<TcpSocket, SandboxError> {
     // Port 0 means the OS gives us an unused port
+    bind_port_guard(0).await
+}
+
+/// Bind the port with SO_REUSEADDR, so `neard` can take it over once the guard is dropped.
+///
+/// Binds `127.0.0.1`, falling back to `::1` on IPv6-only hosts where IPv4 localhost isn't available.
+async fn bind_port_guard(port: u16) -> Result<TcpSocket, SandboxError> {
     // Important to use localhost as using 0.0.0.0 leads to users getting brief firewall popups to
     // allow inbound connections on MacOS.
-    let addr = SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0);
-    let tcp_socket = TcpSocket::new_v4().map_err(|_| TcpError::SocketCreationError)?;
+    match bind_loopback(Ipv4Addr::LOCALHOST.into(), port) {
+        Err(e) if is_address_unavailable(&e) => {
+            tracing::debug!(target: "sandbox", "IPv4 localhost is not available ({e}), binding ::1");
+            bind_loopback(Ipv6Addr::LOCALHOST.into(), port).map_err(|e| {
+                if is_address_unavailable(&e) {
+                    TcpError::LoopbackUnavailable(port)
+                } else {
+                    e
+                }
+            })
+        }
+        result => result,
+    }
+    .map_err(Into::into)
+}
+
+fn bind_loopback(ip: IpAddr, port: u16) -> Result<TcpSocket, TcpError> {
+    let addr = SocketAddr::new(ip, port);
+    let tcp_socket = match ip {
+        IpAddr::V4(_) => TcpSocket::new_v4(),
+        IpAddr::V6(_) => TcpSocket::new_v6(),
+    }
+    .map_err(|_| TcpError::SocketCreationError)?;
 
     // Use SO_REUSEADDR to allow neard to bind the port immediatelly after we release it here
     // without waiting for TIME_WAIT timeout.
@@ -37,25 +210,125 @@ async fn pick_unused_port_guard() -> Result<TcpSocket, SandboxError> {
         .map_err(|_| TcpError::SocketSetReuseAddrError)?;
 
     tcp_socket
-        .bind(std::net::SocketAddr::V4(addr))
-        .map_err(|e| TcpError::BindError(addr.port(), e))?;
+        .bind(addr)
+        .map_err(|e| TcpError::BindError(port, e))?;
 
     Ok(tcp_socket)
 }
 
+/// Whether the bind failed because the host doesn't support the address family or has no such
+/// loopback address, as opposed to the port being taken
+fn is_address_unavailable(error: &TcpError) -> bool {
+    match error {
+        TcpError::SocketCreationError => true,
+        TcpError::BindError(_, e) => e.kind() == std::io::ErrorKind::AddrNotAvailable,
+        _ => false,
+    }
+}
+
+/// tmpfs mounted on most of the Linux distributions, used for [`SandboxConfig::in_memory_store`]
+const IN_MEMORY_DIR: &str = "/dev/shm";
+
+/// Directory to create the home dir in, `None` for the system temp dir
+fn home_dir_root(config: &SandboxConfig) -> Option<PathBuf> {
+    if config.temp_dir_root.is_some() || !config.in_memory_store {
+        return config.temp_dir_root.clone();
+    }
+
+    let in_memory_dir = Path::new(IN_MEMORY_DIR);
+    if cfg!(target_os = "linux") && in_memory_dir.is_dir() {
+        Some(in_memory_dir.to_owned())
+    } else {
+        warn!(target: "sandbox", "{IN_MEMORY_DIR} is not available, the sandbox state is stored in the temp dir");
+        None
+    }
+}
+
+fn copy_dir_all(src: &Path, dest: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(dest)?;
+    for entry in std::fs::read_dir(src)? {
+        let entry = entry?;
+        let dest = dest.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir_all(&entry.path(), &dest)?;
+        } else {
+            std::fs::copy(entry.path(), dest)?;
+        }
+    }
+    Ok(())
+}
+
+/// Instructions written next to the home dir exported with [`Sandbox::export_home`]
+const EXPORT_README: &str = "# Exported near-sandbox node
+
+Run it from this directory with the same neard sandbox binary it was exported from:
+
+    neard --home . run
+
+The RPC listens on the `rpc.addr` of `config.json`, which is `0.0.0.0:3030` as set by `neard init`,
+so the node can be reached from outside of a container. The sandbox itself passes a loopback address
+with `--rpc-addr` instead, do the same to keep the node local:
+
+    neard --home . run --rpc-addr 127.0.0.1:3030
+
+The keys of the sandbox accounts are in the `*.json` files next to `genesis.json`.
+";
+
+/// Resolve the directory where port lock files are stored.
+///
+/// Lock files have to be shared between processes, so the directory is picked in the following order:
+/// [`SandboxConfig::lock_dir`], `NEAR_SANDBOX_LOCK_DIR` env var, system temp dir. If the temp dir is
+/// not writable (e.g. read-only temp in locked-down CI containers), `$HOME/.near` is used instead.
+fn resolve_lock_dir(configured: Option<&Path>) -> Result<PathBuf, SandboxError> {
+    let explicit = configured
+        .map(Path::to_path_buf)
+        .or_else(|| std::env::var_os("NEAR_SANDBOX_LOCK_DIR").map(PathBuf::from));
+    if let Some(dir) = explicit {
+        ensure_writable_dir(&dir)?;
+        return Ok(dir);
+    }
+
+    let temp_dir = std::env::temp_dir();
+    let Err(temp_err) = ensure_writable_dir(&temp_dir) else {
+        return Ok(temp_dir);
+    };
+
+    let Some(home_dir) = std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".near"))
+    else {
+        return Err(temp_err);
+    };
+
+    warn!(
+        target: "sandbox",
+        "Temp dir {} is not writable, storing port locks in {}",
+        temp_dir.display(),
+        home_dir.display()
+    );
+    ensure_writable_dir(&home_dir)?;
+    Ok(home_dir)
+}
+
+fn ensure_writable_dir(dir: &Path) -> Result<(), SandboxError> {
+    std::fs::create_dir_all(dir)
+        .and_then(|_| tempfile::tempfile_in(dir))
+        .map_err(|e| TcpError::LockDirError(dir.to_path_buf(), e))?;
+    Ok(())
+}
+
 /// Acquire an unused port that is bound with TcpListener, and lock it for the duration until the sandbox server has
 /// been started.
-async fn acquire_unused_port_guard() -> Result<(TcpSocket, File), SandboxError> {
+async fn acquire_unused_port_guard(lock_dir: &Path) -> Result<(TcpSocket, File), SandboxError> {
     loop {
         let port_guard = pick_unused_port_guard().await?;
-        let lockpath = std::env::temp_dir().join(format!(
+        let lockpath = lock_dir.join(format!(
             "near-sandbox-port{}.lock",
             port_guard
                 .local_addr()
                 .map_err(TcpError::LocalAddrError)?
                 .port()
         ));
-        let lockfile = File::create(lockpath).map_err(TcpError::LockingError)?;
+        let lockfile = File::create(lockpath)
+            .map_err(|e| TcpError::LockDirError(lock_dir.to_path_buf(), e))?;
         if lockfile.try_lock_exclusive().unwrap_or(false) {
             break Ok((port_guard, lockfile));
         }
@@ -64,28 +337,20 @@ async fn acquire_unused_port_guard() -> Result<(TcpSocket, File), SandboxError>
 
 /// Try to acquire a specific port and lock it.
 /// Returns the port and lock file if successful.
-async fn try_acquire_specific_port_guard(port: u16) -> Result<(TcpSocket, File), SandboxError> {
-    let addr = SocketAddrV4::new(Ipv4Addr::LOCALHOST, port);
-    let tcp_socket = TcpSocket::new_v4().map_err(|_| TcpError::SocketCreationError)?;
-
-    // Use SO_REUSEADDR to allow neard to bind the port immediatelly after we release it here
-    // without waiting for TIME_WAIT timeout.
-    // More details: https://stackoverflow.com/questions/14388706/how-do-so-reuseaddr-and-so-reuseport-differ/14388707#14388707
-    tcp_socket
-        .set_reuseaddr(true)
-        .map_err(|_| TcpError::SocketSetReuseAddrError)?;
-
-    tcp_socket
-        .bind(std::net::SocketAddr::V4(addr))
-        .map_err(|e| TcpError::BindError(addr.port(), e))?;
+async fn try_acquire_specific_port_guard(
+    port: u16,
+    lock_dir: &Path,
+) -> Result<(TcpSocket, File), SandboxError> {
+    let tcp_socket = bind_port_guard(port).await?;
 
     let port = tcp_socket
         .local_addr()
         .map_err(TcpError::LocalAddrError)?
         .port();
 
-    let lockpath = std::env::temp_dir().join(format!("near-sandbox-port{port}.lock"));
-    let lockfile = File::create(&lockpath).map_err(TcpError::LockingError)?;
+    let lockpath = lock_dir.join(format!("near-sandbox-port{port}.lock"));
+    let lockfile =
+        File::create(&lockpath).map_err(|e| TcpError::LockDirError(lock_dir.to_path_buf(), e))?;
     let locked = lockfile
         .try_lock_exclusive()
         .map_err(TcpError::LockingError)?;
@@ -100,12 +365,33 @@ async fn try_acquire_specific_port_guard(port: u16) -> Result<(TcpSocket, File),
     Ok((tcp_socket, lockfile))
 }
 
+/// Scan the range for a port that is both free and not locked by another process.
+async fn acquire_port_guard_in_range(
+    range: &RangeInclusive<u16>,
+    lock_dir: &Path,
+) -> Result<(TcpSocket, File), SandboxError> {
+    for port in range.clone() {
+        match try_acquire_specific_port_guard(port, lock_dir).await {
+            Ok(guard) => return Ok(guard),
+            Err(SandboxError::TcpError(TcpError::BindError(..) | TcpError::LockingError(_))) => {
+                continue;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    Err(TcpError::PortRangeExhausted(range.clone()).into())
+}
+
 async fn acquire_or_lock_port(
     configured_port: Option<u16>,
+    port_range: Option<&RangeInclusive<u16>>,
+    lock_dir: &Path,
 ) -> Result<(TcpSocket, File), SandboxError> {
-    match configured_port {
-        Some(port) => try_acquire_specific_port_guard(port).await,
-        None => acquire_unused_port_guard().await,
+    match (configured_port, port_range) {
+        (Some(port), _) => try_acquire_specific_port_guard(port, lock_dir).await,
+        (None, Some(range)) => acquire_port_guard_in_range(range, lock_dir).await,
+        (None, None) => acquire_unused_port_guard(lock_dir).await,
     }
 }
 
@@ -117,19 +403,51 @@ async fn acquire_or_lock_port(
 pub struct Sandbox {
     /// Home directory for sandbox instance. Will be cleaned up once Sandbox is dropped
     pub home_dir: TempDir,
-    /// URL that can be used to access RPC. In format of `http://127.0.0.1:{port}`
-    pub rpc_addr: String,
+    /// RPC connection to the sandbox. [`Sandbox`] derefs to it, so all the RPC helpers are available directly
+    connection: ConnectedSandbox,
     /// File lock preventing other processes from using the same RPC port until this sandbox is started
     pub rpc_port_lock: File,
     /// File lock preventing other processes from using the same network port until this sandbox is started
     pub net_port_lock: File,
-    /// Sandboxed neard process
-    process: Child,
+    rpc_port: u16,
+    net_port: u16,
+    /// Path of the sandbox binary, used to relaunch the process on [`Sandbox::reset_state`]
+    bin_path: PathBuf,
+    /// Sandboxed neard process. Behind a lock, as it's replaced on [`Sandbox::reset_state`]
+    process: Arc<Mutex<SandboxProcess>>,
+    /// neard logs, if captured with [`SandboxConfig::capture_logs`]
+    logs: Option<CapturedLogs>,
+    /// Extra environment of the neard process, kept to relaunch it on [`Sandbox::reset_state`]
+    env: HashMap<String, String>,
+    /// Command the neard process is run through, kept to relaunch it on [`Sandbox::reset_state`]
+    command_wrapper: Option<Vec<String>>,
+}
+
+// `Sandbox` is shared behind `Arc` across tasks, e.g. in the singleton pattern. All the mutable state is behind
+// locks that are never held across an `.await`, and the HTTP client is a thread-safe `ureq::Agent`.
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Sandbox>();
+    assert_send_sync::<ConnectedSandbox>();
+};
+
+struct SandboxProcess {
+    child: Child,
     /// Internal sandbox cleanup guard for statically stored [`Sandbox`]
     #[cfg(feature = "singleton_cleanup")]
     _sandbox_guard: CleanupGuard,
 }
 
+impl SandboxProcess {
+    fn new(child: Child) -> Self {
+        Self {
+            #[cfg(feature = "singleton_cleanup")]
+            _sandbox_guard: CleanupGuard::new(child.id().expect("sandbox process must have PID")),
+            child,
+        }
+    }
+}
+
 impl Sandbox {
     /// Start a new sandbox with the default near-sandbox-utils version.
     ///
@@ -149,7 +467,7 @@ impl Sandbox {
     pub async fn start_sandbox() -> Result<Self, SandboxError> {
         Self::start_sandbox_with_config_and_version(
             SandboxConfig::default(),
-            crate::DEFAULT_NEAR_SANDBOX_VERSION,
+            crate::NEAR_SANDBOX_VERSION,
         )
         .await
     }
@@ -176,6 +494,32 @@ impl Sandbox {
         Self::start_sandbox_with_config_and_version(SandboxConfig::default(), version).await
     }
 
+    /// Start a new sandbox with the binary built by the nearcore CI for the `master` commit.
+    ///
+    /// `commit` is the full 40 character hash, or `{branch}/{hash}` for the commits of other branches.
+    /// Binaries are usually published within 12h after the commit is pushed, until then
+    /// [`SandboxError::ArtifactNotFound`] is returned.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use near_sandbox::*;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let sandbox =
+    ///     Sandbox::start_sandbox_with_commit("0123456789abcdef0123456789abcdef01234567").await?;
+    /// println!("Sandbox RPC endpoint: {}", sandbox.rpc_addr);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn start_sandbox_with_commit(commit: &str) -> Result<Self, SandboxError> {
+        Self::start_sandbox_with_config_and_version(
+            SandboxConfig::default(),
+            &commit_version(commit)?,
+        )
+        .await
+    }
+
     /// Start a new sandbox with the custom configuration and default version.
     ///
     /// # Arguments
@@ -193,12 +537,12 @@ impl Sandbox {
     /// cfg.rpc_port = Some(3030);
     /// cfg.additional_genesis = Some(json!({ "epoch_length": 200 }));
     /// cfg.additional_accounts = vec![
-    ///     GenesisAccount {
-    ///         account_id: "bob.near".parse().unwrap(),
-    ///         public_key: "ed25519:...".to_string(),
-    ///         private_key: "ed25519:...".to_string(),
-    ///         balance: NearToken::from_near(10_000),
-    ///     },
+    ///     GenesisAccount::new(
+    ///         "bob.near".parse().unwrap(),
+    ///         "ed25519:...".to_string(),
+    ///         "ed25519:...".to_string(),
+    ///         NearToken::from_near(10_000),
+    ///     ),
     /// ];
     ///
     /// let sandbox = Sandbox::start_sandbox_with_config(cfg).await?;
@@ -207,8 +551,7 @@ impl Sandbox {
     /// # }
     /// ```
     pub async fn start_sandbox_with_config(config: SandboxConfig) -> Result<Self, SandboxError> {
-        Self::start_sandbox_with_config_and_version(config, crate::DEFAULT_NEAR_SANDBOX_VERSION)
-            .await
+        Self::start_sandbox_with_config_and_version(config, crate::NEAR_SANDBOX_VERSION).await
     }
 
     /// Start a new sandbox with a custom configuration and specific near-sandbox-utils version.
@@ -229,12 +572,12 @@ impl Sandbox {
     /// cfg.rpc_port = Some(3030);
     /// cfg.additional_genesis = Some(json!({ "epoch_length": 200 }));
     /// cfg.additional_accounts = vec![
-    ///     GenesisAccount {
-    ///         account_id: "bob.near".parse().unwrap(),
-    ///         public_key: "ed25519:...".to_string(),
-    ///         private_key: "ed25519:...".to_string(),
-    ///         balance: NearToken::from_near(10_000),
-    ///     },
+    ///     GenesisAccount::new(
+    ///         "bob.near".parse().unwrap(),
+    ///         "ed25519:...".to_string(),
+    ///         "ed25519:...".to_string(),
+    ///         NearToken::from_near(10_000),
+    ///     ),
     /// ];
     ///
     /// let sandbox = Sandbox::start_sandbox_with_config_and_version(cfg, "2.6.3").await?;
@@ -245,12 +588,60 @@ impl Sandbox {
     pub async fn start_sandbox_with_config_and_version(
         config: SandboxConfig,
         version: &str,
+    ) -> Result<Self, SandboxError> {
+        config.validate()?;
+        let bin_path = if config.force_download {
+            crate::runner::reinstall(version, config.http_agent.as_ref())?
+        } else {
+            ensure_sandbox_bin_with_version(version, config.http_agent.as_ref())?
+        };
+
+        Self::start_sandbox_with_bin(config, bin_path).await
+    }
+
+    /// Start a new sandbox with a custom configuration, running the neard binary at `bin_path`.
+    ///
+    /// Unlike `NEAR_SANDBOX_BIN_PATH`, which applies to all the sandboxes of the process, the binary
+    /// is used only for this sandbox, so sandboxes running different local builds of neard can be
+    /// started concurrently. [`SandboxConfig::force_download`] has no effect here.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use near_sandbox::*;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let sandbox = Sandbox::start_sandbox_with_config_and_bin_path(
+    ///     SandboxConfig::default(),
+    ///     "../nearcore/target/release/neard".into(),
+    /// )
+    /// .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn start_sandbox_with_config_and_bin_path(
+        config: SandboxConfig,
+        bin_path: PathBuf,
+    ) -> Result<Self, SandboxError> {
+        config.validate()?;
+        if !bin_path.exists() {
+            return Err(SandboxError::BinaryError(format!(
+                "{} does not exists",
+                bin_path.display()
+            )));
+        }
+
+        Self::start_sandbox_with_bin(config, bin_path).await
+    }
+
+    async fn start_sandbox_with_bin(
+        config: SandboxConfig,
+        bin_path: PathBuf,
     ) -> Result<Self, SandboxError> {
         suppress_sandbox_logs_if_required();
-        let home_dir = Self::init_home_dir_with_version(version).await?;
+        let home_dir = Self::init_home_dir(&bin_path, &config).await?;
 
-        config::set_sandbox_configs_with_config(&home_dir, &config)?;
-        config::set_sandbox_genesis_with_config(&home_dir, &config)?;
+        config::set_sandbox_home_with_config(&home_dir, &config)?;
 
         let max_num_port_retries = config
             .port_transfer_retries
@@ -263,20 +654,39 @@ impl Sandbox {
 
         let max_num_port_retries = max_num_port_retries.max(1);
 
+        let agent = config
+            .http_agent
+            .clone()
+            .unwrap_or_else(ureq::Agent::new_with_defaults);
+        let lock_dir = resolve_lock_dir(config.lock_dir.as_deref())?;
+        let logs = config.capture_logs.then(|| {
+            CapturedLogs::new(
+                config
+                    .log_buffer_bytes
+                    .unwrap_or(config::DEFAULT_LOG_BUFFER_BYTES),
+            )
+        });
+
         for attempt in 1..=max_num_port_retries {
-            let (rpc_guard, rpc_port_lock) = acquire_or_lock_port(config.rpc_port).await?;
-            let (net_guard, net_port_lock) = acquire_or_lock_port(config.net_port).await?;
-
-            let rpc_addr = crate::runner::rpc_socket(
-                rpc_guard
-                    .local_addr()
-                    .map_err(TcpError::LocalAddrError)?
-                    .port(),
-            );
+            let (rpc_guard, rpc_port_lock) =
+                acquire_or_lock_port(config.rpc_port, config.rpc_port_range.as_ref(), &lock_dir)
+                    .await?;
+            let (net_guard, net_port_lock) =
+                acquire_or_lock_port(config.net_port, None, &lock_dir).await?;
+
+            let rpc_socket = rpc_guard.local_addr().map_err(TcpError::LocalAddrError)?;
+            let rpc_port = rpc_socket.port();
+            let net_port = net_guard
+                .local_addr()
+                .map_err(TcpError::LocalAddrError)?
+                .port();
+            let rpc_addr = rpc_socket.to_string();
 
             // NOTE: We the silence output to `stderr` of the `neard` up until last retry, so we
             // don't confuse user in case there is port collision during retries.
-            let stderr_for_child = if attempt < max_num_port_retries {
+            let stderr_for_child = if logs.is_some() {
+                Some(Stdio::piped())
+            } else if attempt < max_num_port_retries {
                 Some(Stdio::null())
             } else {
                 None
@@ -284,44 +694,56 @@ impl Sandbox {
 
             let mut child = run_neard_with_port_guards(
                 home_dir.path(),
-                version,
+                &bin_path,
                 rpc_guard,
                 net_guard,
                 stderr_for_child,
+                &config.env,
+                config.command_wrapper.as_deref(),
             )?;
+            if let Some(logs) = &logs {
+                logs.capture(&mut child);
+            }
 
             info!(target: "sandbox", "Attempting to start a sandbox at {} with pid={:?}", rpc_addr, child.id());
 
             let rpc_addr = format!("http://{rpc_addr}");
 
-            match Self::wait_until_ready(&rpc_addr).await {
+            let rpc_timeout = config.rpc_timeout.unwrap_or(config::DEFAULT_RPC_TIMEOUT);
+            let startup = if config.take_forced_startup_timeout() {
+                Err(SandboxError::TimeoutError)
+            } else {
+                Self::wait_for_startup(&rpc_addr, rpc_timeout, &agent).await
+            };
+            match startup {
                 Ok(()) => {
                     info!(target: "sandbox", "Started up sandbox at {} with pid={:?}", rpc_addr, child.id());
 
-                    let sandbox: Self;
-                    #[cfg(feature = "singleton_cleanup")]
-                    {
-                        let sandbox_guard =
-                            CleanupGuard::new(child.id().expect("sandbox process must have PID"));
-
-                        sandbox = Self {
-                            home_dir,
-                            rpc_addr,
-                            rpc_port_lock,
-                            net_port_lock,
-                            process: child,
-                            _sandbox_guard: sandbox_guard,
-                        };
-                    }
-                    #[cfg(not(feature = "singleton_cleanup"))]
-                    {
-                        sandbox = Self {
-                            home_dir,
+                    let sandbox = Self {
+                        home_dir,
+                        connection: ConnectedSandbox::new(
                             rpc_addr,
-                            rpc_port_lock,
-                            net_port_lock,
-                            process: child,
-                        };
+                            rpc_timeout,
+                            config.rpc_retries.unwrap_or(config::DEFAULT_RPC_RETRIES),
+                            agent,
+                            config::max_payload_size(&config),
+                        ),
+                        rpc_port_lock,
+                        net_port_lock,
+                        rpc_port,
+                        net_port,
+                        bin_path,
+                        process: Arc::new(Mutex::new(SandboxProcess::new(child))),
+                        logs,
+                        env: config.env.clone(),
+                        command_wrapper: config.command_wrapper.clone(),
+                    };
+
+                    if let Some(on_ready) = &config.on_ready {
+                        on_ready
+                            .call(&sandbox)
+                            .await
+                            .map_err(|e| SandboxError::SetupError(e.to_string()))?;
                     }
 
                     return Ok(sandbox);
@@ -366,253 +788,2118 @@ impl Sandbox {
         );
     }
 
-    async fn init_home_dir_with_version(version: &str) -> Result<TempDir, SandboxError> {
-        let home_dir = tempfile::tempdir().map_err(SandboxError::FileError)?;
+    /// Port the sandbox RPC is listening on
+    pub const fn rpc_port(&self) -> u16 {
+        self.rpc_port
+    }
 
-        let output = init_with_version(&home_dir, version)?
-            .wait_with_output()
-            .await
-            .map_err(SandboxError::RuntimeError)?;
-        info!(target: "sandbox", "sandbox init: {:?}", output);
+    /// Port the sandbox uses for the network (p2p) connections
+    pub const fn net_port(&self) -> u16 {
+        self.net_port
+    }
 
-        Ok(home_dir)
+    /// Reset the chain state back to genesis.
+    ///
+    /// nearcore doesn't have an RPC for that, so `neard` is stopped, its database is wiped and the node
+    /// is started again with the same config, genesis, keys and ports. [`ConnectedSandbox::rpc_addr`] stays the same,
+    /// so existing RPC clients remain valid. RPC requests sent while the node restarts will fail.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use near_sandbox::*;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let sandbox = Sandbox::start_sandbox().await?;
+    /// sandbox.fast_forward(100).await?;
+    ///
+    /// sandbox.reset_state().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn reset_state(&self) -> Result<(), SandboxError> {
+        info!(target: "sandbox", "Resetting sandbox state at {}", self.rpc_addr);
+
+        self.stop_neard().await?;
+
+        let data_dir = self.home_dir.path().join("data");
+        if data_dir.exists() {
+            std::fs::remove_dir_all(data_dir).map_err(SandboxError::FileError)?;
+        }
+        self.lock_touched_accounts().clear();
+
+        self.restart_neard().await
     }
 
-    async fn wait_until_ready(rpc: &str) -> Result<(), SandboxError> {
-        let timeout_secs = std::env::var("NEAR_RPC_TIMEOUT_SECS").map_or(10, |secs| {
-            secs.parse::<u64>()
-                .expect("Failed to parse NEAR_RPC_TIMEOUT_SECS")
-        });
+    /// Copy the home dir of the sandbox (config, genesis, keys and the RocksDB data) to `dest`, so the
+    /// seeded state can be run as a standalone node, e.g. in a container for a demo.
+    ///
+    /// `neard` is stopped for the copy, so the database is consistent, and started again afterwards the same
+    /// way as in [`Self::reset_state`]. RPC requests sent meanwhile will fail. A `README.md` with the command
+    /// to run the exported node is written to `dest` as well.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use near_sandbox::*;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let sandbox = Sandbox::start_sandbox().await?;
+    /// sandbox.create_account("alice.sandbox".parse()?).send().await?;
+    /// sandbox.export_home(std::path::Path::new("./demo-node")).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn export_home(&self, dest: &Path) -> Result<(), SandboxError> {
+        info!(target: "sandbox", "Exporting sandbox home dir to {}", dest.display());
 
-        let mut interval = tokio::time::interval(Duration::from_millis(500));
-        let status_url = format!("{rpc}/status");
-        for _ in 0..timeout_secs * 2 {
-            interval.tick().await;
-            let url = status_url.clone();
-            let response = tokio::task::spawn_blocking(move || ureq::get(&url).call())
-                .await
-                .map_err(|e| SandboxError::RuntimeError(std::io::Error::other(e)))?;
-            if response.is_ok() {
+        self.stop_neard().await?;
+        let exported = copy_dir_all(self.home_dir.path(), dest)
+            .and_then(|()| std::fs::write(dest.join("README.md"), EXPORT_README))
+            .map_err(SandboxError::FileError);
+        self.restart_neard().await?;
+
+        exported
+    }
+
+    async fn stop_neard(&self) -> Result<(), SandboxError> {
+        if let Err(e) = self.lock_process().child.start_kill() {
+            tracing::debug!(target: "sandbox", "Kill returned error (may already be dead): {}", e);
+        }
+        loop {
+            let exited = self
+                .lock_process()
+                .child
+                .try_wait()
+                .map_err(SandboxError::ShutdownError)?;
+            if exited.is_some() {
                 return Ok(());
             }
+            tokio::time::sleep(Duration::from_millis(50)).await;
         }
-        Err(SandboxError::TimeoutError)
     }
 
-    async fn get_block_height(&self) -> Result<u64, SandboxRpcError> {
-        let response = self
-            .send_request(
-                &self.rpc_addr,
-                serde_json::json!({
-                    "jsonrpc": "2.0",
-                    "id": "0",
-                    "method": "status",
-                }),
-            )
-            .await?;
+    /// Start `neard` again in the same home dir and on the same ports
+    async fn restart_neard(&self) -> Result<(), SandboxError> {
+        let mut child = run_neard_with_port_guards(
+            self.home_dir.path(),
+            &self.bin_path,
+            bind_port_guard(self.rpc_port).await?,
+            bind_port_guard(self.net_port).await?,
+            self.logs.as_ref().map(|_| Stdio::piped()),
+            &self.env,
+            self.command_wrapper.as_deref(),
+        )?;
+        if let Some(logs) = &self.logs {
+            logs.capture(&mut child);
+        }
+        *self.lock_process() = SandboxProcess::new(child);
 
-        response
-            .get("result")
-            .and_then(|r| r.get("sync_info"))
-            .and_then(|s| s.get("latest_block_height"))
-            .and_then(|h| h.as_u64())
-            .ok_or(SandboxRpcError::UnexpectedResponse)
+        Self::wait_for_startup(&self.rpc_addr, self.rpc_timeout(), &self.agent).await
     }
 
-    pub async fn fast_forward(&self, blocks: u64) -> Result<(), SandboxRpcError> {
-        let initial_height = self.get_block_height().await?;
-        let target_height = initial_height + blocks;
-
-        self.send_request(
-            &self.rpc_addr,
-            serde_json::json!({
-                "jsonrpc": "2.0",
-                "id": "0",
-                "method": "sandbox_fast_forward",
-                "params": {
-                    "delta_height": blocks,
-                },
-            }),
-        )
-        .await?;
-
-        // Poll until blocks are produced (30 second timeout)
-        let timeout = Duration::from_secs(30);
-        let start = std::time::Instant::now();
-        let mut interval = tokio::time::interval(Duration::from_millis(100));
+    /// Change the timeout of the RPC requests made through this sandbox,
+    /// initially set with [`SandboxConfig::rpc_timeout`]
+    pub const fn set_rpc_timeout(&mut self, timeout: Duration) {
+        self.connection.set_rpc_timeout(timeout);
+    }
 
-        loop {
-            interval.tick().await;
+    /// Change the number of retries of the RPC requests made through this sandbox,
+    /// initially set with [`SandboxConfig::rpc_retries`]
+    pub const fn set_rpc_retries(&mut self, retries: usize) {
+        self.connection.set_rpc_retries(retries);
+    }
 
-            if start.elapsed() > timeout {
-                return Err(SandboxRpcError::SandboxRpcError(format!(
-                    "fast_forward timeout: expected height {} but current height is {}",
-                    target_height,
-                    self.get_block_height().await.unwrap_or(0)
-                )));
-            }
+    fn lock_process(&self) -> MutexGuard<'_, SandboxProcess> {
+        self.process.lock().unwrap_or_else(PoisonError::into_inner)
+    }
 
-            match self.get_block_height().await {
-                Ok(height) if height >= target_height => return Ok(()),
-                _ => continue,
+    /// Paths of the key files written to the home dir for the genesis accounts.
+    ///
+    /// Every file is named `{account_id}.json` and contains `account_id`, `public_key` and `private_key`.
+    pub fn account_key_files(&self) -> Result<Vec<PathBuf>, SandboxError> {
+        let mut files = vec![];
+        for entry in std::fs::read_dir(self.home_dir.path()).map_err(SandboxError::FileError)? {
+            let path = entry.map_err(SandboxError::FileError)?.path();
+            if path.extension().is_some_and(|ext| ext == "json")
+                && config::read_account_key_file(&path)?.is_some()
+            {
+                files.push(path);
             }
         }
+        files.sort();
+
+        Ok(files)
     }
 
-    pub const fn patch_state(&self, account_id: AccountId) -> PatchState<'_> {
-        PatchState::new(account_id, self)
+    /// Public and private keys of the genesis account, if the sandbox was started with it.
+    pub fn account_key(
+        &self,
+        account_id: &AccountId,
+    ) -> Result<Option<(String, String)>, SandboxError> {
+        let path = self.home_dir.path().join(format!("{account_id}.json"));
+        Ok(config::read_account_key_file(path)?
+            .filter(|key| &key.account_id == account_id)
+            .map(|key| (key.public_key, key.private_key)))
     }
 
-    /// Helper function to simplify importing an account from an RPC endpoint
-    /// into the sandbox. By default, the account will add [crate::config::DEFAULT_GENESIS_ACCOUNT_PUBLIC_KEY] as the full access public key.
+    /// Accounts known to exist in the sandbox, sorted by account id.
     ///
-    /// # Arguments
-    /// * `account_id` - the account id to import
-    /// * `from_rpc` - the RPC endpoint to fetch the account from
+    /// nearcore has no RPC to enumerate the accounts, so these are the accounts from the genesis
+    /// records (the default ones, [`SandboxConfig::additional_accounts`] and the node's validator account)
+    /// and the [`ConnectedSandbox::touched_accounts`].
     ///
     /// # Example
+    ///
     /// ```rust,no_run
     /// use near_sandbox::*;
     ///
     /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
     /// let sandbox = Sandbox::start_sandbox().await?;
-    /// let account_id = "user.testnet".parse()?;
-    /// sandbox.import_account("https://rpc.testnet.near.org", account_id).send().await?;
+    /// for account_id in sandbox.list_accounts()? {
+    ///     println!("{account_id}: {}", sandbox.account_balance(&account_id).await?);
+    /// }
     /// # Ok(())
     /// # }
     /// ```
-    pub fn import_account(
-        &self,
-        from_rpc: impl AsRef<str>,
-        account_id: AccountId,
-    ) -> AccountImport<'_> {
-        AccountImport::new(account_id, from_rpc.as_ref().to_string(), self)
+    pub fn list_accounts(&self) -> Result<Vec<AccountId>, SandboxError> {
+        let mut accounts: BTreeSet<_> = config::read_genesis_account_ids(self.home_dir.path())?
+            .into_iter()
+            .collect();
+        accounts.extend(self.touched_accounts());
+
+        Ok(accounts.into_iter().collect())
     }
 
-    /// Creates a new account in the sandbox. By default, the account will have [crate::config::DEFAULT_GENESIS_ACCOUNT_BALANCE]
-    /// and will have [crate::config::DEFAULT_GENESIS_ACCOUNT_PRIVATE_KEY] as the full access private key.
+    /// Attach to an already running sandbox instead of spawning a new one.
     ///
-    /// # Arguments
-    /// * `account_id` - the account id to create
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use near_sandbox::*;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let sandbox = Sandbox::connect("http://127.0.0.1:3030").await?;
+    /// sandbox.fast_forward(10).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn connect(rpc_addr: &str) -> Result<ConnectedSandbox, SandboxError> {
+        let sandbox = ConnectedSandbox::new(
+            rpc_addr.trim_end_matches('/').to_owned(),
+            config::DEFAULT_RPC_TIMEOUT,
+            config::DEFAULT_RPC_RETRIES,
+            ureq::Agent::new_with_defaults(),
+            config::NEARCORE_MAX_PAYLOAD_SIZE,
+        );
+        sandbox
+            .status()
+            .await
+            .map_err(|e| SandboxError::ConnectionError(format!("{}: {e}", sandbox.rpc_addr)))?;
+
+        Ok(sandbox)
+    }
+
+    async fn init_home_dir(
+        bin_path: &Path,
+        config: &SandboxConfig,
+    ) -> Result<TempDir, SandboxError> {
+        let test_seed = config
+            .deterministic
+            .then_some(config::DETERMINISTIC_TEST_SEED);
+        let home_dir = match home_dir_root(config) {
+            Some(root) => tempfile::tempdir_in(root),
+            None => tempfile::tempdir(),
+        }
+        .map_err(SandboxError::FileError)?;
+
+        let output = init(&home_dir, bin_path, test_seed, &config.env)?
+            .wait_with_output()
+            .await
+            .map_err(SandboxError::RuntimeError)?;
+        info!(target: "sandbox", "sandbox init: {:?}", output);
+
+        if !output.status.success() {
+            return Err(SandboxError::InitError(
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+            ));
+        }
+
+        Ok(home_dir)
+    }
+
+    async fn wait_for_startup(
+        rpc: &str,
+        rpc_timeout: Duration,
+        agent: &ureq::Agent,
+    ) -> Result<(), SandboxError> {
+        let timeout_secs = std::env::var("NEAR_RPC_TIMEOUT_SECS").map_or(10, |secs| {
+            secs.parse::<u64>()
+                .expect("Failed to parse NEAR_RPC_TIMEOUT_SECS")
+        });
+
+        wait_for_status(
+            rpc,
+            rpc_timeout,
+            agent,
+            Instant::now() + Duration::from_secs(timeout_secs),
+        )
+        .await
+    }
+}
+
+impl Deref for Sandbox {
+    type Target = ConnectedSandbox;
+
+    fn deref(&self) -> &Self::Target {
+        &self.connection
+    }
+}
+
+/// Handle to an already running sandbox, e.g. started by another process or with `near-sandbox run`.
+///
+/// Provides the same RPC helpers as [`Sandbox`], but doesn't own the process, so it won't be stopped
+/// once the handle is dropped. Can be created with [`Sandbox::connect`].
+#[derive(Debug, Clone)]
+pub struct ConnectedSandbox {
+    /// URL that can be used to access RPC. In format of `http://127.0.0.1:{port}`,
+    /// or `http://[::1]:{port}` on IPv6-only hosts
+    pub rpc_addr: String,
+    rpc_timeout: Duration,
+    rpc_retries: usize,
+    /// HTTP client shared by all the requests, so connections to the RPC are kept alive and reused
+    agent: ureq::Agent,
+    /// Maximum size of the requests accepted by the RPC, used to split large state patches
+    max_payload_size: usize,
+    /// Accounts changed with the state patches sent through this handle or its clones
+    touched_accounts: Arc<Mutex<BTreeSet<AccountId>>>,
+    /// Nonces of the keys signing the transactions sent through this handle or its clones
+    nonces: Arc<NonceCache>,
+}
+
+impl ConnectedSandbox {
+    fn new(
+        rpc_addr: String,
+        rpc_timeout: Duration,
+        rpc_retries: usize,
+        agent: ureq::Agent,
+        max_payload_size: usize,
+    ) -> Self {
+        Self {
+            rpc_addr,
+            rpc_timeout,
+            rpc_retries,
+            agent,
+            max_payload_size,
+            touched_accounts: Arc::default(),
+            nonces: Arc::default(),
+        }
+    }
+
+    /// Accounts created, imported or patched through this handle or its clones, sorted by account id.
+    ///
+    /// Tracks [`Self::create_account`], [`Self::import_account`], [`Self::patch_state`] and the helpers
+    /// built on them. Accounts changed with transactions or by another handle of the same sandbox aren't included.
     ///
     /// # Example
     ///
     /// ```rust,no_run
     /// use near_sandbox::*;
-    /// use near_token::NearToken;
     ///
     /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
     /// let sandbox = Sandbox::start_sandbox().await?;
-    /// let account_id = "user.testnet".parse()?;
-    /// sandbox.create_account(account_id)
-    ///     .initial_balance(NearToken::from_near(1))
-    ///     .public_key("ed25519:...".to_string())
-    ///     .send()
+    /// sandbox.create_account("alice.sandbox".parse()?).send().await?;
+    /// assert_eq!(sandbox.touched_accounts(), vec!["alice.sandbox".parse()?]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn touched_accounts(&self) -> Vec<AccountId> {
+        self.lock_touched_accounts().iter().cloned().collect()
+    }
+
+    pub(crate) fn track_accounts<'a>(&self, account_ids: impl IntoIterator<Item = &'a AccountId>) {
+        self.lock_touched_accounts()
+            .extend(account_ids.into_iter().cloned());
+    }
+
+    fn lock_touched_accounts(&self) -> MutexGuard<'_, BTreeSet<AccountId>> {
+        self.touched_accounts
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Wait until the RPC of the sandbox responds to the `status` requests, polling it until `deadline`.
+    ///
+    /// Useful when orchestrating sandboxes, e.g. to wait for a sandbox started by another process
+    /// before [`Sandbox::connect`]ing to it, or to give a slow machine more time than the startup allows.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use std::time::{Duration, Instant};
+    /// use near_sandbox::*;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let sandbox = Sandbox::start_sandbox().await?;
+    /// sandbox
+    ///     .wait_until_ready(Instant::now() + Duration::from_secs(60))
     ///     .await?;
     /// # Ok(())
     /// # }
     /// ```
-    pub const fn create_account(&self, account_id: AccountId) -> AccountCreation<'_> {
-        AccountCreation::new(account_id, self)
+    pub async fn wait_until_ready(&self, deadline: Instant) -> Result<(), SandboxError> {
+        wait_for_status(&self.rpc_addr, self.rpc_timeout, &self.agent, deadline).await
     }
 
-    async fn send_request(
+    /// HTTP client used for the requests to the sandbox, with a pool of keep-alive connections.
+    ///
+    /// It's cheap to clone and can be used for custom requests to get the connection reuse for free.
+    pub const fn rpc_client(&self) -> &ureq::Agent {
+        &self.agent
+    }
+
+    /// Replace the HTTP client used for the requests, initially set with [`SandboxConfig::http_agent`].
+    ///
+    /// Useful to route the requests of a [`Sandbox::connect`]ed sandbox through a proxy.
+    pub fn set_rpc_client(&mut self, agent: ureq::Agent) {
+        self.agent = agent;
+    }
+
+    /// Call the RPC method of the sandbox with [`Self::rpc_client`] and return the `result`.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use near_sandbox::*;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let sandbox = Sandbox::start_sandbox().await?;
+    /// let block = sandbox
+    ///     .rpc_call("block", serde_json::json!({ "finality": "final" }))
+    ///     .await?;
+    /// println!("{}", block["header"]["height"]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn rpc_call(
         &self,
-        rpc: impl AsRef<str>,
-        json_body: serde_json::Value,
+        method: &str,
+        params: serde_json::Value,
     ) -> Result<serde_json::Value, SandboxRpcError> {
-        let url = rpc.as_ref().to_string();
-        let body_json = json_body.clone();
+        let mut response = self
+            .send_request(
+                &self.rpc_addr,
+                serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "id": "0",
+                    "method": method,
+                    "params": params,
+                }),
+            )
+            .await?;
 
-        let response = tokio::task::spawn_blocking(move || {
-            ureq::post(&url)
-                .content_type("application/json")
-                .send_json(&body_json)
-        })
-        .await
-        .map_err(|e| {
-            let io_err = std::io::Error::other(e.to_string());
-            ureq::Error::from(io_err)
-        })??;
+        response
+            .get_mut("result")
+            .map(serde_json::Value::take)
+            .ok_or(SandboxRpcError::UnexpectedResponse)
+    }
 
-        let body: serde_json::Value = response.into_body().read_json()?;
+    /// Timeout applied to every RPC request, see [`SandboxConfig::rpc_timeout`]
+    pub const fn rpc_timeout(&self) -> Duration {
+        self.rpc_timeout
+    }
 
-        if let Some(error) = body.get("error") {
-            return Err(SandboxRpcError::SandboxRpcError(error.to_string()));
-        }
+    /// Change the timeout applied to every RPC request. Defaults to [`config::DEFAULT_RPC_TIMEOUT`]
+    /// for [`Sandbox::connect`].
+    pub const fn set_rpc_timeout(&mut self, timeout: Duration) {
+        self.rpc_timeout = timeout;
+    }
 
-        Ok(body)
+    /// Number of retries of the RPC requests failed with a transient error, see [`SandboxConfig::rpc_retries`]
+    pub const fn rpc_retries(&self) -> usize {
+        self.rpc_retries
     }
-}
 
-impl Drop for Sandbox {
-    fn drop(&mut self) {
-        info!(
-            target: "sandbox",
-            "Cleaning up sandbox: pid={:?}",
-            self.process.id()
-        );
+    /// Change the number of retries of the RPC requests. Defaults to [`config::DEFAULT_RPC_RETRIES`]
+    /// for [`Sandbox::connect`], `0` disables the retries.
+    pub const fn set_rpc_retries(&mut self, retries: usize) {
+        self.rpc_retries = retries;
+    }
 
-        if let Err(e) = self.process.start_kill() {
-            tracing::debug!(target: "sandbox", "Kill returned error (may already be dead): {}", e);
-        }
+    /// Maximum size of the requests accepted by the RPC in bytes, i.e. its `json_payload_max_size`.
+    ///
+    /// Set with [`SandboxConfig::max_payload_size`] or `NEAR_SANDBOX_MAX_PAYLOAD_SIZE` and 1GB by default.
+    /// For [`Sandbox::connect`] the config of the sandbox is unknown, so it's the nearcore default of 10MB.
+    pub const fn max_payload_size(&self) -> usize {
+        self.max_payload_size
+    }
+
+    /// Result of the `status` RPC method
+    pub async fn status(&self) -> Result<serde_json::Value, SandboxRpcError> {
+        let response = self
+            .send_request(
+                &self.rpc_addr,
+                serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "id": "0",
+                    "method": "status",
+                }),
+            )
+            .await?;
 
-        let _ = self.process.try_wait();
+        response
+            .get("result")
+            .cloned()
+            .ok_or(SandboxRpcError::UnexpectedResponse)
     }
-}
 
-/// Turn off neard-sandbox logs by default. Users can turn them back on with
-/// NEAR_ENABLE_SANDBOX_LOG=1 and specify further parameters with the custom
-/// NEAR_SANDBOX_LOG for higher levels of specificity. NEAR_SANDBOX_LOG args
-/// will be forward into RUST_LOG environment variable as to not conflict
-/// with similar named log targets.
-fn suppress_sandbox_logs_if_required() {
-    if let Ok(val) = std::env::var("NEAR_ENABLE_SANDBOX_LOG") {
-        if val != "0" {
-            return;
-        }
+    /// Latest block of the sandbox, the typed `sync_info` of [`Self::status`]
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use near_sandbox::*;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let sandbox = Sandbox::start_sandbox().await?;
+    /// let sync_info = sandbox.sync_info().await?;
+    /// println!("#{} {}", sync_info.latest_block_height, sync_info.latest_block_hash);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn sync_info(&self) -> Result<SyncInfo, SandboxRpcError> {
+        let mut status = self.status().await?;
+        serde_json::from_value(status["sync_info"].take())
+            .map_err(|_| SandboxRpcError::UnexpectedResponse)
     }
 
-    // non-exhaustive list of targets to suppress, since choosing a default LogLevel
-    // does nothing in this case, since nearcore seems to be overriding it somehow:
+    async fn get_block_height(&self) -> Result<u64, SandboxRpcError> {
+        Ok(self.sync_info().await?.latest_block_height)
+    }
 
-    // SAFETY: well, overall, it might be unsafe, but I think it's fine here.
-    // As the worst case scenario is that the logs are not suppressed, which is not a big deal.
-    unsafe {
-        std::env::set_var("NEAR_SANDBOX_LOG", "near=error,stats=error,network=error");
+    pub async fn fast_forward(&self, blocks: u64) -> Result<(), SandboxRpcError> {
+        let initial_height = self.get_block_height().await?;
+        let target_height = initial_height + blocks;
+
+        self.send_request(
+            &self.rpc_addr,
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": "0",
+                "method": "sandbox_fast_forward",
+                "params": {
+                    "delta_height": blocks,
+                },
+            }),
+        )
+        .await?;
+
+        // Poll until blocks are produced
+        let timeout = blocks_timeout(self.rpc_timeout, blocks, FAST_FORWARD_BLOCK_TIMEOUT);
+        let start = std::time::Instant::now();
+        let mut interval = tokio::time::interval(fast_forward_poll_interval(blocks));
+
+        loop {
+            interval.tick().await;
+
+            if start.elapsed() > timeout {
+                return Err(SandboxRpcError::SandboxRpcError(format!(
+                    "fast_forward timeout: expected height {} but current height is {}",
+                    target_height,
+                    self.get_block_height().await.unwrap_or(0)
+                )));
+            }
+
+            match self.get_block_height().await {
+                Ok(height) if height >= target_height => return Ok(()),
+                _ => continue,
+            }
+        }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Fast-forward to the first block of the next epoch.
+    ///
+    /// The epoch length is taken from [`Self::protocol_config`] and the start of the current epoch from
+    /// the `validators` RPC method, so the boundary is right even if the genesis overrides the epoch length.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use near_sandbox::*;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let sandbox = Sandbox::start_sandbox().await?;
+    /// sandbox.fast_forward_to_next_epoch().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn fast_forward_to_next_epoch(&self) -> Result<(), SandboxRpcError> {
+        let epoch_length = self.protocol_config().await?.epoch_length;
+        let next_epoch_start = self.epoch_start_height().await? + epoch_length;
+        let height = self.get_block_height().await?;
+
+        // NOTE: The boundary might be already reached while the epoch switch isn't visible yet
+        if next_epoch_start > height {
+            self.fast_forward(next_epoch_start - height).await?;
+        }
+        Ok(())
+    }
 
-    #[tokio::test]
-    async fn test_fast_forward() {
-        let sandbox = Sandbox::start_sandbox().await.unwrap();
-        let network =
-            near_api::NetworkConfig::from_rpc_url("sandbox", sandbox.rpc_addr.parse().unwrap());
+    async fn epoch_start_height(&self) -> Result<u64, SandboxRpcError> {
+        self.rpc_call("validators", serde_json::json!([null]))
+            .await?
+            .get("epoch_start_height")
+            .and_then(|height| height.as_u64())
+            .ok_or(SandboxRpcError::UnexpectedResponse)
+    }
 
-        let height = near_api::Chain::block_number()
-            .fetch_from(&network)
-            .await
-            .unwrap();
+    /// Wait until the sandbox produces `blocks` more blocks through the regular block production.
+    ///
+    /// Unlike [`Self::fast_forward`], which jumps the height with the `sandbox_fast_forward` shortcut,
+    /// every block goes through the normal pipeline, so receipts and delayed receipt queues are processed
+    /// the same way as on a real network. It's much slower though, as it takes real time to produce blocks.
+    pub async fn produce_blocks(&self, blocks: u64) -> Result<(), SandboxRpcError> {
+        let initial_height = self.get_block_height().await?;
+        let target_height = initial_height + blocks;
 
-        sandbox.fast_forward(1000).await.unwrap();
+        let timeout = blocks_timeout(self.rpc_timeout, blocks, PRODUCE_BLOCK_TIMEOUT);
+        let start = std::time::Instant::now();
+        let mut interval = tokio::time::interval(Duration::from_millis(100));
 
-        let new_height = near_api::Chain::block_number()
-            .fetch_from(&network)
-            .await
-            .unwrap();
+        loop {
+            interval.tick().await;
 
-        assert!(
-            new_height >= height + 1000,
+            let height = self.get_block_height().await;
+            match height {
+                Ok(height) if height >= target_height => return Ok(()),
+                _ if start.elapsed() > timeout => {
+                    return Err(SandboxRpcError::SandboxRpcError(format!(
+                        "produce_blocks timeout: expected height {} but current height is {}",
+                        target_height,
+                        height.unwrap_or(0)
+                    )));
+                }
+                _ => continue,
+            }
+        }
+    }
+
+    pub const fn patch_state(&self, account_id: AccountId) -> PatchState<'_> {
+        PatchState::new(account_id, self)
+    }
+
+    /// Set the liquid balance of an existing account, e.g. to refill the genesis account drained by earlier tests.
+    ///
+    /// Shortcut for [`Self::patch_state`] with [`PatchState::initial_balance`], the rest of the account is kept.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use near_sandbox::*;
+    /// use near_token::NearToken;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let sandbox = Sandbox::start_sandbox().await?;
+    /// sandbox
+    ///     .set_balance(&"sandbox".parse()?, NearToken::from_near(10_000))
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn set_balance(
+        &self,
+        account_id: &AccountId,
+        balance: NearToken,
+    ) -> Result<(), SandboxRpcError> {
+        self.patch_state(account_id.clone())
+            .initial_balance(balance)
+            .send()
+            .await
+    }
+
+    /// Transfer `amount` to the account from the genesis account, with a real signed transaction.
+    ///
+    /// Unlike [`Self::create_account`], which patches the state, this goes through the actual
+    /// transfer path. A missing account is created by the transfer: implicit accounts on their own, named
+    /// ones with [`config::DEFAULT_GENESIS_ACCOUNT_PUBLIC_KEY`] as the full access key. Only sub-accounts
+    /// of the genesis account and top-level accounts of at least 32 characters can be created this way.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use near_sandbox::*;
+    /// use near_token::NearToken;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let sandbox = Sandbox::start_sandbox().await?;
+    /// sandbox
+    ///     .fund(&"alice.sandbox".parse()?, NearToken::from_near(5))
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn fund(
+        &self,
+        account_id: &AccountId,
+        amount: NearToken,
+    ) -> Result<(), SandboxRpcError> {
+        let transfer = Action::Transfer(amount.as_yoctonear());
+        let actions = if is_implicit_account(account_id) || self.account_exists(account_id).await? {
+            vec![transfer]
+        } else {
+            let signing_key = parse_secret_key(config::DEFAULT_GENESIS_ACCOUNT_PRIVATE_KEY)?;
+            vec![
+                Action::CreateAccount,
+                transfer,
+                Action::AddFullAccessKey(signing_key.verifying_key().to_bytes()),
+            ]
+        };
+
+        self.sign_and_send(
+            &config::DEFAULT_GENESIS_ACCOUNT.to_owned(),
+            config::DEFAULT_GENESIS_ACCOUNT_PRIVATE_KEY,
+            account_id,
+            &actions,
+        )
+        .await?
+        .into_result()?;
+
+        Ok(())
+    }
+
+    /// Helper function to simplify importing an account from an RPC endpoint
+    /// into the sandbox. By default, the account will add [crate::config::DEFAULT_GENESIS_ACCOUNT_PUBLIC_KEY] as the full access public key.
+    ///
+    /// # Arguments
+    /// * `account_id` - the account id to import
+    /// * `from_rpc` - the RPC endpoint to fetch the account from
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// use near_sandbox::*;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let sandbox = Sandbox::start_sandbox().await?;
+    /// let account_id = "user.testnet".parse()?;
+    /// sandbox.import_account("https://rpc.testnet.near.org", account_id).send().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn import_account(
+        &self,
+        from_rpc: impl AsRef<str>,
+        account_id: AccountId,
+    ) -> AccountImport<'_> {
+        AccountImport::new(account_id, from_rpc.as_ref().to_string(), self)
+    }
+
+    /// Import a working copy of a contract from an RPC endpoint: the account, its code and the whole storage.
+    ///
+    /// Same as [`Self::import_account`] with [`AccountImport::with_storage`].
+    ///
+    /// NOTE: RPC nodes refuse to view the state of contracts larger than their `trie_viewer_state_size_limit`
+    /// (50KB by default), which fails with [`SandboxRpcError::ContractStateTooLarge`]. Import such contracts
+    /// from a node with a higher limit, e.g. a self-hosted one.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// use near_sandbox::*;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let sandbox = Sandbox::start_sandbox().await?;
+    /// let contract_id = "wrap.near".parse()?;
+    /// sandbox
+    ///     .import_contract("https://rpc.mainnet.near.org", contract_id)
+    ///     .fetch_at(BlockReference::Final)
+    ///     .send()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn import_contract(
+        &self,
+        from_rpc: impl AsRef<str>,
+        account_id: AccountId,
+    ) -> AccountImport<'_> {
+        self.import_account(from_rpc, account_id).with_storage()
+    }
+
+    /// Import only the access keys of the account from an RPC endpoint, leaving the account,
+    /// code and storage in the sandbox intact.
+    ///
+    /// Useful to sign transactions as a real account that was already patched into the sandbox.
+    /// Use [`Self::patch_state`] with [`FetchData::access_keys`] to pin the block or pass an API key.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// use near_sandbox::*;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let sandbox = Sandbox::start_sandbox().await?;
+    /// let account_id = "user.testnet".parse()?;
+    /// sandbox.import_access_keys(account_id, "https://rpc.testnet.near.org").await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn import_access_keys(
+        &self,
+        account_id: AccountId,
+        from_rpc: impl AsRef<str>,
+    ) -> Result<(), SandboxRpcError> {
+        self.patch_state(account_id)
+            .fetch_from(from_rpc, FetchData::NONE.access_keys())
+            .await?
+            .send()
+            .await
+    }
+
+    /// Creates a new account in the sandbox. By default, the account will have [crate::config::DEFAULT_GENESIS_ACCOUNT_BALANCE]
+    /// and will have [crate::config::DEFAULT_GENESIS_ACCOUNT_PRIVATE_KEY] as the full access private key.
+    ///
+    /// # Arguments
+    /// * `account_id` - the account id to create
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use near_sandbox::*;
+    /// use near_token::NearToken;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let sandbox = Sandbox::start_sandbox().await?;
+    /// let account_id = "user.testnet".parse()?;
+    /// sandbox.create_account(account_id)
+    ///     .initial_balance(NearToken::from_near(1))
+    ///     .public_key("ed25519:...".to_string())
+    ///     .send()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub const fn create_account(&self, account_id: AccountId) -> AccountCreation<'_> {
+        AccountCreation::new(account_id, self)
+    }
+
+    /// Dump the accounts with their code, storage and access keys as state records.
+    ///
+    /// All the accounts are fetched at the same final block, so the records are consistent with each other.
+    /// Serialized with `serde_json`, they make a fixture that can be replayed with [`PatchState::state_record`]
+    /// or put into the `records` of a genesis.
+    ///
+    /// NOTE: The storage is fetched with `view_state`, which is limited by the node config, so it works for
+    /// accounts with a moderate amount of state only.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use near_sandbox::*;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let sandbox = Sandbox::start_sandbox().await?;
+    /// let records = sandbox.export_state(&["sandbox".parse()?]).await?;
+    /// std::fs::write("fixture.json", serde_json::to_string_pretty(&records)?)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn export_state(
+        &self,
+        accounts: &[AccountId],
+    ) -> Result<Vec<StateRecord>, SandboxRpcError> {
+        let block = self.block(BlockReference::Final).await?;
+
+        let mut records = vec![];
+        for account_id in accounts {
+            let patch = self
+                .patch_state(account_id.clone())
+                .fetch_at(BlockReference::Hash(block.hash.clone()))
+                .fetch_from(&self.rpc_addr, FetchData::ALL)
+                .await?;
+            records.extend(patch.state);
+        }
+
+        Ok(records)
+    }
+
+    /// Add the account to the running sandbox with exactly the same records as
+    /// [`SandboxConfig::additional_accounts`] get in the genesis.
+    ///
+    /// Unlike [`Self::create_account`], which clones a template account, the `code_hash` and
+    /// `storage_usage` match the ones of genesis accounts, so storage staking behaves the same.
+    /// The total supply of the chain is not updated though.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use near_sandbox::*;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let sandbox = Sandbox::start_sandbox().await?;
+    /// let account = GenesisAccount::default_with_name("user.sandbox".parse()?);
+    /// sandbox.apply_genesis_patch(&account).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn apply_genesis_patch(
+        &self,
+        account: &GenesisAccount,
+    ) -> Result<(), SandboxRpcError> {
+        account
+            .state_records()
+            .into_iter()
+            .fold(
+                self.patch_state(account.account_id.clone()),
+                PatchState::state_record,
+            )
+            .send()
+            .await
+    }
+
+    /// Create the accounts with `balance` each in a single state patch, instead of a transaction or
+    /// a patch per account. All of them get the genesis-style records of [`Self::apply_genesis_patch`]
+    /// and the default full access key, and are returned with their keys to sign transactions with.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use near_sandbox::*;
+    /// use near_token::NearToken;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let sandbox = Sandbox::start_sandbox().await?;
+    /// let account_ids = (0..50)
+    ///     .map(|i| format!("user{i}.sandbox").parse())
+    ///     .collect::<Result<Vec<_>, _>>()?;
+    /// for account in sandbox.create_accounts(&account_ids, NearToken::from_near(10)).await? {
+    ///     println!("{} signs with {}", account.account_id, account.private_key);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn create_accounts(
+        &self,
+        account_ids: &[AccountId],
+        balance: NearToken,
+    ) -> Result<Vec<GenesisAccount>, SandboxRpcError> {
+        let accounts: Vec<GenesisAccount> = account_ids
+            .iter()
+            .map(|account_id| GenesisAccount {
+                balance,
+                ..GenesisAccount::default_with_name(account_id.clone())
+            })
+            .collect();
+        let Some(first) = accounts.first() else {
+            return Ok(accounts);
+        };
+
+        accounts
+            .iter()
+            .flat_map(GenesisAccount::state_records)
+            .fold(
+                self.patch_state(first.account_id.clone()),
+                PatchState::state_record,
+            )
+            .send()
+            .await?;
+
+        Ok(accounts)
+    }
+
+    pub(crate) async fn send_request(
+        &self,
+        rpc: impl AsRef<str>,
+        json_body: serde_json::Value,
+    ) -> Result<serde_json::Value, SandboxRpcError> {
+        self.send_request_with_headers(rpc, &[], json_body).await
+    }
+
+    async fn send_request_with_headers(
+        &self,
+        rpc: impl AsRef<str>,
+        headers: &[(String, String)],
+        json_body: serde_json::Value,
+    ) -> Result<serde_json::Value, SandboxRpcError> {
+        // Jumping the height twice would break the expectations of the caller
+        let retries = if json_body["method"] == "sandbox_fast_forward" {
+            0
+        } else {
+            self.rpc_retries
+        };
+
+        let mut attempt = 0;
+        loop {
+            let result = self.post_json(rpc.as_ref(), headers, &json_body).await;
+            let transient = match &result {
+                Ok(body) => body.get("error").is_some_and(is_rpc_timeout_error),
+                Err(e) => is_transient_rpc_error(e),
+            };
+
+            if transient && attempt < retries {
+                let backoff = RPC_RETRY_BACKOFF * 2u32.saturating_pow(attempt as u32);
+                attempt += 1;
+                warn!(
+                    target: "sandbox",
+                    "RPC request failed with a transient error, retrying {}/{} in {:?}",
+                    attempt,
+                    retries,
+                    backoff
+                );
+                tokio::time::sleep(backoff).await;
+                continue;
+            }
+
+            let body = result?;
+            if let Some(error) = body.get("error") {
+                return Err(SandboxRpcError::SandboxRpcError(error.to_string()));
+            }
+
+            return Ok(body);
+        }
+    }
+
+    async fn post_json(
+        &self,
+        url: &str,
+        headers: &[(String, String)],
+        json_body: &serde_json::Value,
+    ) -> Result<serde_json::Value, SandboxRpcError> {
+        let url = url.to_string();
+        let body_json = json_body.clone();
+        let headers = headers.to_vec();
+        let timeout = self.rpc_timeout;
+        let agent = self.agent.clone();
+
+        let response = tokio::task::spawn_blocking(move || {
+            let mut request = agent
+                .post(&url)
+                .config()
+                .timeout_global(Some(timeout))
+                .build()
+                .content_type("application/json");
+            for (name, value) in &headers {
+                request = request.header(name, value);
+            }
+            request.send_json(&body_json)
+        })
+        .await
+        .map_err(|e| {
+            let io_err = std::io::Error::other(e.to_string());
+            ureq::Error::from(io_err)
+        })??;
+
+        Ok(response.into_body().read_json()?)
+    }
+}
+
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        let process = &mut self.lock_process().child;
+        info!(
+            target: "sandbox",
+            "Cleaning up sandbox: pid={:?}",
+            process.id()
+        );
+
+        if let Err(e) = process.start_kill() {
+            tracing::debug!(target: "sandbox", "Kill returned error (may already be dead): {}", e);
+        }
+
+        let _ = process.try_wait();
+    }
+}
+
+/// Turn off neard-sandbox logs by default. Users can turn them back on with
+/// NEAR_ENABLE_SANDBOX_LOG=1 and specify further parameters with the custom
+/// NEAR_SANDBOX_LOG for higher levels of specificity. NEAR_SANDBOX_LOG args
+/// will be forward into RUST_LOG environment variable as to not conflict
+/// with similar named log targets.
+fn suppress_sandbox_logs_if_required() {
+    if let Ok(val) = std::env::var("NEAR_ENABLE_SANDBOX_LOG") {
+        if val != "0" {
+            return;
+        }
+    }
+
+    // non-exhaustive list of targets to suppress, since choosing a default LogLevel
+    // does nothing in this case, since nearcore seems to be overriding it somehow:
+
+    // SAFETY: well, overall, it might be unsafe, but I think it's fine here.
+    // As the worst case scenario is that the logs are not suppressed, which is not a big deal.
+    unsafe {
+        std::env::set_var("NEAR_SANDBOX_LOG", "near=error,stats=error,network=error");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fault_injection;
+
+    /// Empty wasm module
+    pub(crate) const EMPTY_CONTRACT: &[u8] = b"\0asm\x01\0\0\0";
+
+    /// Smallest contract exporting a no-op `new` method
+    pub(crate) const NOOP_CONTRACT: &[u8] = b"\0asm\x01\0\0\0\x01\x04\x01\x60\0\0\x03\x02\x01\0\x07\x07\x01\x03new\0\0\x0a\x04\x01\x02\0\x0b";
+
+    /// Genesis account with [`EMPTY_CONTRACT`] deployed and the given storage entries
+    pub(crate) fn contract_account(
+        account_id: &str,
+        storage: impl IntoIterator<Item = (Vec<u8>, Vec<u8>)>,
+    ) -> config::GenesisAccount {
+        storage.into_iter().fold(
+            config::GenesisAccount {
+                account_id: account_id.parse().unwrap(),
+                ..Default::default()
+            }
+            .with_code(EMPTY_CONTRACT.to_vec()),
+            |account, (key, value)| account.with_storage_entry(key, value),
+        )
+    }
+
+    /// Contract whose `get` method returns `{"name":"sandbox","id":7}`
+    pub(crate) const JSON_CONTRACT: &[u8] = b"\0asm\x01\0\0\0\x01\x09\x02\x60\x02\x7e\x7e\0\x60\0\0\x02\x22\x02\x03env\x0cvalue_return\0\0\x03env\x06memory\x02\0\x01\x03\x02\x01\x01\x07\x07\x01\x03get\0\x01\x0a\x0a\x01\x08\0\x42\x19\x42\0\x10\0\x0b\x0b\x1f\x01\0\x41\0\x0b\x19{\"name\":\"sandbox\",\"id\":7}";
+
+    /// Sandbox started with the `account` in its genesis
+    pub(crate) async fn sandbox_with_account(account: &config::GenesisAccount) -> Sandbox {
+        Sandbox::start_sandbox_with_config(SandboxConfig {
+            additional_accounts: vec![account.clone()],
+            ..Default::default()
+        })
+        .await
+        .unwrap()
+    }
+
+    /// Sandbox started with the [`contract_account`] in its genesis
+    pub(crate) async fn sandbox_with_contract(
+        account_id: &str,
+        storage: impl IntoIterator<Item = (Vec<u8>, Vec<u8>)>,
+    ) -> (Sandbox, config::GenesisAccount) {
+        let account = contract_account(account_id, storage);
+        (sandbox_with_account(&account).await, account)
+    }
+
+    #[tokio::test]
+    async fn test_rpc_and_net_ports() {
+        let sandbox = Sandbox::start_sandbox().await.unwrap();
+        assert!(
+            sandbox
+                .rpc_addr
+                .ends_with(&format!(":{}", sandbox.rpc_port()))
+        );
+        assert_ne!(sandbox.rpc_port(), sandbox.net_port());
+    }
+
+    #[tokio::test]
+    async fn test_account_key_files() {
+        let sandbox = Sandbox::start_sandbox().await.unwrap();
+        let default_account = config::GenesisAccount::default();
+        assert_eq!(
+            sandbox.account_key(&default_account.account_id).unwrap(),
+            Some((default_account.public_key, default_account.private_key))
+        );
+        assert_eq!(sandbox.account_key_files().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_fast_forward() {
+        let sandbox = Sandbox::start_sandbox().await.unwrap();
+        let network =
+            near_api::NetworkConfig::from_rpc_url("sandbox", sandbox.rpc_addr.parse().unwrap());
+
+        let height = near_api::Chain::block_number()
+            .fetch_from(&network)
+            .await
+            .unwrap();
+
+        sandbox.fast_forward(1000).await.unwrap();
+
+        let new_height = near_api::Chain::block_number()
+            .fetch_from(&network)
+            .await
+            .unwrap();
+
+        assert!(
+            new_height >= height + 1000,
             "expected new height({}) to be at least 1000 blocks higher than the original height({})",
             new_height,
             height
         );
     }
 
+    #[test]
+    fn test_commit_version() {
+        let hash = "0123456789ABCDEF0123456789abcdef01234567";
+        assert_eq!(
+            commit_version(hash).unwrap(),
+            "master/0123456789abcdef0123456789abcdef01234567"
+        );
+        assert_eq!(
+            commit_version(&format!("release/{hash}")).unwrap(),
+            "release/0123456789abcdef0123456789abcdef01234567"
+        );
+        assert!(commit_version("0123456").is_err());
+        assert!(commit_version(&format!("/{hash}")).is_err());
+    }
+
+    #[test]
+    fn test_is_transient_rpc_error() {
+        let request_error = |e: ureq::Error| SandboxRpcError::RequestError(Box::new(e));
+
+        assert!(is_transient_rpc_error(&request_error(
+            ureq::Error::ConnectionFailed
+        )));
+        assert!(is_transient_rpc_error(&request_error(ureq::Error::Io(
+            std::io::ErrorKind::ConnectionReset.into()
+        ))));
+        assert!(is_transient_rpc_error(&request_error(
+            ureq::Error::StatusCode(503)
+        )));
+        assert!(!is_transient_rpc_error(&request_error(
+            ureq::Error::StatusCode(400)
+        )));
+        assert!(!is_transient_rpc_error(&SandboxRpcError::SandboxRpcError(
+            "METHOD_NOT_FOUND".to_owned()
+        )));
+
+        assert!(is_rpc_timeout_error(&serde_json::json!({
+            "code": -32000,
+            "name": "HANDLER_ERROR",
+            "cause": { "name": "TIMEOUT_ERROR" },
+        })));
+        assert!(!is_rpc_timeout_error(&serde_json::json!({
+            "code": -32000,
+            "name": "HANDLER_ERROR",
+            "cause": { "name": "UNKNOWN_ACCOUNT" },
+        })));
+    }
+
+    #[tokio::test]
+    async fn test_rpc_retries() {
+        let mut sandbox = Sandbox::start_sandbox_with_config(SandboxConfig {
+            rpc_retries: Some(1),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        assert_eq!(sandbox.rpc_retries(), 1);
+
+        // Logical errors fail right away
+        let error = sandbox
+            .rpc_call("no_such_method", serde_json::json!({}))
+            .await;
+        assert!(matches!(error, Err(SandboxRpcError::SandboxRpcError(_))));
+
+        sandbox.set_rpc_retries(0);
+        assert_eq!(sandbox.rpc_retries(), 0);
+        sandbox.fast_forward(1).await.unwrap();
+    }
+
+    #[test]
+    fn test_fast_forward_poll_interval() {
+        assert_eq!(fast_forward_poll_interval(1), Duration::from_millis(100));
+        assert_eq!(fast_forward_poll_interval(1000), Duration::from_millis(500));
+        assert_eq!(
+            fast_forward_poll_interval(1_000_000),
+            Duration::from_secs(1)
+        );
+    }
+
+    #[test]
+    fn test_blocks_timeout() {
+        let rpc_timeout = Duration::from_secs(1);
+        assert_eq!(
+            blocks_timeout(rpc_timeout, 10_000, FAST_FORWARD_BLOCK_TIMEOUT),
+            Duration::from_secs(51)
+        );
+        assert_eq!(
+            blocks_timeout(rpc_timeout, 10, PRODUCE_BLOCK_TIMEOUT),
+            Duration::from_secs(21)
+        );
+        assert_eq!(
+            blocks_timeout(rpc_timeout, u64::MAX, PRODUCE_BLOCK_TIMEOUT),
+            Duration::MAX
+        );
+    }
+
+    #[tokio::test]
+    async fn test_bind_port_guard() {
+        let guard = bind_port_guard(0).await.unwrap();
+        assert!(guard.local_addr().unwrap().ip().is_loopback());
+
+        assert!(is_address_unavailable(&TcpError::BindError(
+            0,
+            std::io::ErrorKind::AddrNotAvailable.into()
+        )));
+        assert!(!is_address_unavailable(&TcpError::BindError(
+            0,
+            std::io::ErrorKind::AddrInUse.into()
+        )));
+    }
+
+    #[tokio::test]
+    async fn test_fast_forward_to_next_epoch() {
+        let sandbox = Sandbox::start_sandbox_with_config(SandboxConfig {
+            additional_genesis: Some(serde_json::json!({ "epoch_length": 20 })),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        let epoch_start = sandbox.epoch_start_height().await.unwrap();
+        sandbox.fast_forward_to_next_epoch().await.unwrap();
+
+        assert!(sandbox.get_block_height().await.unwrap() >= epoch_start + 20);
+        sandbox.produce_blocks(1).await.unwrap();
+        assert_eq!(
+            sandbox.epoch_start_height().await.unwrap(),
+            epoch_start + 20
+        );
+    }
+
+    #[tokio::test]
+    async fn test_produce_blocks() {
+        let sandbox = Sandbox::start_sandbox().await.unwrap();
+
+        let height = sandbox.get_block_height().await.unwrap();
+        sandbox.produce_blocks(3).await.unwrap();
+
+        assert!(sandbox.get_block_height().await.unwrap() >= height + 3);
+    }
+
+    #[tokio::test]
+    async fn test_http_agent() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        let agent: ureq::Agent = ureq::Agent::config_builder()
+            .middleware(
+                move |req: ureq::http::Request<ureq::SendBody>,
+                      next: ureq::middleware::MiddlewareNext| {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    next.handle(req)
+                },
+            )
+            .build()
+            .into();
+        let sandbox = Sandbox::start_sandbox_with_config(SandboxConfig {
+            http_agent: Some(agent.clone()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let before = requests.load(Ordering::SeqCst);
+        assert!(before > 0, "the readiness checks must go through the agent");
+        sandbox.status().await.unwrap();
+        assert_eq!(requests.load(Ordering::SeqCst), before + 1);
+
+        let mut connected = Sandbox::connect(&sandbox.rpc_addr).await.unwrap();
+        connected.set_rpc_client(agent);
+        let before = requests.load(Ordering::SeqCst);
+        connected.status().await.unwrap();
+        assert_eq!(requests.load(Ordering::SeqCst), before + 1);
+    }
+
+    #[tokio::test]
+    async fn test_connect_to_running_sandbox() {
+        let sandbox = Sandbox::start_sandbox().await.unwrap();
+        let connected = Sandbox::connect(&format!("{}/", sandbox.rpc_addr))
+            .await
+            .unwrap();
+        assert_eq!(connected.rpc_addr, sandbox.rpc_addr);
+
+        let height = connected.get_block_height().await.unwrap();
+        connected.fast_forward(5).await.unwrap();
+        drop(connected);
+
+        assert!(sandbox.get_block_height().await.unwrap() >= height + 5);
+    }
+
+    #[tokio::test]
+    async fn test_deterministic_keys() {
+        let config = SandboxConfig {
+            deterministic: true,
+            ..Default::default()
+        };
+        let validator_key = |sandbox: &Sandbox| {
+            let key = std::fs::read_to_string(sandbox.home_dir.path().join("validator_key.json"))
+                .unwrap();
+            serde_json::from_str::<serde_json::Value>(&key).unwrap()["public_key"].clone()
+        };
+
+        let first = Sandbox::start_sandbox_with_config(config.clone())
+            .await
+            .unwrap();
+        let second = Sandbox::start_sandbox_with_config(config).await.unwrap();
+
+        assert_eq!(validator_key(&first), validator_key(&second));
+    }
+
+    #[tokio::test]
+    async fn test_genesis_contract() {
+        let (sandbox, account) =
+            sandbox_with_contract("contract.sandbox", [(b"STATE".to_vec(), b"value".to_vec())])
+                .await;
+
+        let summary = sandbox.account_summary(&account.account_id).await.unwrap();
+        assert_eq!(summary.code_hash, account.code_hash());
+        assert_eq!(summary.storage_usage, account.storage_usage());
+    }
+
+    #[tokio::test]
+    async fn test_apply_genesis_patch() {
+        let sandbox = Sandbox::start_sandbox().await.unwrap();
+        let account = contract_account("runtime.sandbox", [(b"STATE".to_vec(), b"value".to_vec())]);
+
+        sandbox.apply_genesis_patch(&account).await.unwrap();
+
+        let summary = sandbox.account_summary(&account.account_id).await.unwrap();
+        assert_eq!(summary.amount, account.balance);
+        assert_eq!(summary.code_hash, account.code_hash());
+        assert_eq!(summary.storage_usage, account.storage_usage());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_requests() {
+        const NUM_TASKS: usize = 16;
+
+        let sandbox = Arc::new(Sandbox::start_sandbox().await.unwrap());
+        let handles: Vec<_> = (0..NUM_TASKS)
+            .map(|i| {
+                let sandbox = Arc::clone(&sandbox);
+                tokio::spawn(async move {
+                    let account_id: AccountId = format!("user{i}.sandbox").parse().unwrap();
+                    sandbox
+                        .create_account(account_id.clone())
+                        .initial_balance(NearToken::from_near(1))
+                        .send()
+                        .await
+                        .unwrap();
+                    assert_eq!(
+                        sandbox.account_balance(&account_id).await.unwrap(),
+                        NearToken::from_near(1)
+                    );
+                    // Signed by the genesis key shared by all the tasks
+                    sandbox
+                        .fund(&account_id, NearToken::from_near(1))
+                        .await
+                        .unwrap();
+                    assert_eq!(
+                        sandbox.account_balance(&account_id).await.unwrap(),
+                        NearToken::from_near(2)
+                    );
+                    sandbox.sync_info().await.unwrap();
+                    assert_eq!(sandbox.health().await, health::Health::Ready);
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle.await.unwrap();
+        }
+        assert_eq!(sandbox.touched_accounts().len(), NUM_TASKS);
+    }
+
+    #[tokio::test]
+    async fn test_sync_info() {
+        let sandbox = Sandbox::start_sandbox().await.unwrap();
+        let before = sandbox.sync_info().await.unwrap();
+        assert!(!before.syncing);
+        assert!(!before.latest_block_hash.is_empty());
+
+        sandbox.fast_forward(10).await.unwrap();
+        let after = sandbox.sync_info().await.unwrap();
+        assert!(after.latest_block_height >= before.latest_block_height + 10);
+        assert_ne!(after.latest_block_hash, before.latest_block_hash);
+    }
+
+    #[tokio::test]
+    async fn test_set_balance() {
+        let sandbox = Sandbox::start_sandbox().await.unwrap();
+        let account_id = config::DEFAULT_GENESIS_ACCOUNT.to_owned();
+        let code_hash = sandbox
+            .account_summary(&account_id)
+            .await
+            .unwrap()
+            .code_hash;
+
+        sandbox
+            .set_balance(&account_id, NearToken::from_near(3))
+            .await
+            .unwrap();
+        let summary = sandbox.account_summary(&account_id).await.unwrap();
+        assert_eq!(summary.amount, NearToken::from_near(3));
+        assert_eq!(summary.code_hash, code_hash);
+
+        let missing: AccountId = "missing.sandbox".parse().unwrap();
+        assert!(
+            sandbox
+                .set_balance(&missing, NearToken::from_near(1))
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fund() {
+        let sandbox = Sandbox::start_sandbox().await.unwrap();
+        let amount = NearToken::from_near(5);
+
+        // Created by the transfer with the default key
+        let new_account: AccountId = "alice.sandbox".parse().unwrap();
+        sandbox.fund(&new_account, amount).await.unwrap();
+        assert_eq!(sandbox.account_balance(&new_account).await.unwrap(), amount);
+        sandbox
+            .access_key(&new_account, config::DEFAULT_GENESIS_ACCOUNT_PUBLIC_KEY)
+            .await
+            .unwrap();
+
+        // Topped up
+        sandbox.fund(&new_account, amount).await.unwrap();
+        assert_eq!(
+            sandbox.account_balance(&new_account).await.unwrap(),
+            NearToken::from_near(10)
+        );
+
+        let implicit: AccountId = "a".repeat(64).parse().unwrap();
+        assert!(is_implicit_account(&implicit));
+        sandbox.fund(&implicit, amount).await.unwrap();
+        assert_eq!(sandbox.account_balance(&implicit).await.unwrap(), amount);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_fund_concurrently() {
+        const NUM_TASKS: usize = 8;
+
+        let sandbox = Arc::new(Sandbox::start_sandbox().await.unwrap());
+        // All the transfers are signed with the genesis key, so each needs its own nonce
+        let handles: Vec<_> = (0..NUM_TASKS)
+            .map(|i| {
+                let sandbox = Arc::clone(&sandbox);
+                tokio::spawn(async move {
+                    let account_id: AccountId = format!("user{i}.sandbox").parse().unwrap();
+                    sandbox
+                        .fund(&account_id, NearToken::from_near(1))
+                        .await
+                        .unwrap();
+                    account_id
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            let account_id = handle.await.unwrap();
+            assert_eq!(
+                sandbox.account_balance(&account_id).await.unwrap(),
+                NearToken::from_near(1)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_create_accounts() {
+        let sandbox = Sandbox::start_sandbox().await.unwrap();
+        let account_ids: Vec<AccountId> = (0..3)
+            .map(|i| format!("user{i}.sandbox").parse().unwrap())
+            .collect();
+        let balance = NearToken::from_near(7);
+
+        let accounts = sandbox
+            .create_accounts(&account_ids, balance)
+            .await
+            .unwrap();
+        assert_eq!(accounts.len(), account_ids.len());
+        for (account, account_id) in accounts.iter().zip(&account_ids) {
+            assert_eq!(&account.account_id, account_id);
+            assert_eq!(sandbox.account_balance(account_id).await.unwrap(), balance);
+            sandbox
+                .access_key(account_id, &account.public_key)
+                .await
+                .unwrap();
+        }
+        assert_eq!(sandbox.touched_accounts(), account_ids);
+
+        assert!(
+            sandbox
+                .create_accounts(&[], balance)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_create_account_from_missing_template() {
+        let sandbox = Sandbox::start_sandbox().await.unwrap();
+        assert!(
+            sandbox
+                .account_exists(&config::DEFAULT_ACCOUNT_FOR_CLONING.to_owned())
+                .await
+                .unwrap()
+        );
+
+        let result = sandbox
+            .create_account("erin.sandbox".parse().unwrap())
+            .from_template("missing.sandbox".parse().unwrap())
+            .send()
+            .await;
+        assert!(
+            matches!(result, Err(SandboxRpcError::SandboxRpcError(e)) if e.contains("from_template"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_create_account_without_access_key() {
+        let sandbox = Sandbox::start_sandbox().await.unwrap();
+        let account_id: AccountId = "keyless.sandbox".parse().unwrap();
+        sandbox
+            .create_account(account_id.clone())
+            .no_access_key()
+            .send()
+            .await
+            .unwrap();
+
+        let keys = sandbox
+            .query(query::QueryRequest::ViewAccessKeyList { account_id })
+            .await
+            .unwrap();
+        assert_eq!(keys["keys"], serde_json::json!([]));
+    }
+
+    #[tokio::test]
+    async fn test_import_account_as() {
+        let (sandbox, account) =
+            sandbox_with_contract("contract.sandbox", [(b"STATE".to_vec(), b"value".to_vec())])
+                .await;
+
+        let copy_id: AccountId = "copy.sandbox".parse().unwrap();
+        sandbox
+            .import_account(&sandbox.rpc_addr, account.account_id.clone())
+            .with_storage()
+            .as_account(copy_id.clone())
+            .send()
+            .await
+            .unwrap();
+
+        let summary = sandbox.account_summary(&copy_id).await.unwrap();
+        assert_eq!(summary.code_hash, account.code_hash());
+    }
+
+    #[tokio::test]
+    async fn test_access_key_nonce() {
+        let sandbox = Sandbox::start_sandbox().await.unwrap();
+        let account_id: AccountId = "replayed.sandbox".parse().unwrap();
+        sandbox
+            .create_account(account_id.clone())
+            .access_key_nonce(42)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(
+            sandbox
+                .next_nonce(&account_id, config::DEFAULT_GENESIS_ACCOUNT_PUBLIC_KEY)
+                .await
+                .unwrap(),
+            43
+        );
+
+        // Imported keys keep the nonce of the source unless it's overridden
+        let copy_id: AccountId = "copy.sandbox".parse().unwrap();
+        sandbox
+            .import_account(&sandbox.rpc_addr, account_id.clone())
+            .with_access_keys()
+            .as_account(copy_id.clone())
+            .send()
+            .await
+            .unwrap();
+        let key = sandbox
+            .access_key(&copy_id, config::DEFAULT_GENESIS_ACCOUNT_PUBLIC_KEY)
+            .await
+            .unwrap();
+        assert_eq!(key.nonce, 42);
+
+        sandbox
+            .import_account(&sandbox.rpc_addr, account_id)
+            .with_access_keys()
+            .as_account(copy_id.clone())
+            .access_key_nonce(7)
+            .send()
+            .await
+            .unwrap();
+        let key = sandbox
+            .access_key(&copy_id, config::DEFAULT_GENESIS_ACCOUNT_PUBLIC_KEY)
+            .await
+            .unwrap();
+        assert_eq!(key.nonce, 7);
+    }
+
+    #[tokio::test]
+    async fn test_list_accounts() {
+        let account_id: AccountId = "alice.sandbox".parse().unwrap();
+        let sandbox = Sandbox::start_sandbox_with_config(SandboxConfig {
+            additional_accounts: vec![config::GenesisAccount::default_with_name(
+                account_id.clone(),
+            )],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        let accounts = sandbox.list_accounts().unwrap();
+        assert!(accounts.contains(&account_id));
+        assert!(accounts.contains(&config::DEFAULT_GENESIS_ACCOUNT.to_owned()));
+        assert!(accounts.is_sorted());
+
+        let created: AccountId = "bob.sandbox".parse().unwrap();
+        sandbox
+            .create_account(created.clone())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(sandbox.touched_accounts(), vec![created.clone()]);
+        assert!(sandbox.list_accounts().unwrap().contains(&created));
+
+        sandbox.reset_state().await.unwrap();
+        assert!(sandbox.touched_accounts().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_import_contract() {
+        let (source, account) = sandbox_with_contract(
+            "contract.sandbox",
+            (0..50u8).map(|i| (vec![i], vec![i; 32])),
+        )
+        .await;
+        let sandbox = Sandbox::start_sandbox().await.unwrap();
+
+        sandbox
+            .import_contract(&source.rpc_addr, account.account_id.clone())
+            .send()
+            .await
+            .unwrap();
+
+        let records = sandbox
+            .export_state(std::slice::from_ref(&account.account_id))
+            .await
+            .unwrap();
+        let storage = records
+            .iter()
+            .filter(|record| matches!(record, StateRecord::Data { .. }))
+            .count();
+        assert_eq!(storage, account.storage.len());
+        let summary = sandbox.account_summary(&account.account_id).await.unwrap();
+        assert_eq!(summary.code_hash, account.code_hash());
+
+        let limited = Sandbox::start_sandbox_with_config(SandboxConfig {
+            additional_accounts: vec![account.clone()],
+            additional_config: Some(serde_json::json!({ "trie_viewer_state_size_limit": 100 })),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let result = sandbox
+            .import_contract(&limited.rpc_addr, account.account_id.clone())
+            .send()
+            .await;
+        assert!(matches!(
+            result,
+            Err(SandboxRpcError::ContractStateTooLarge(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_reset_state() {
+        let sandbox = Sandbox::start_sandbox().await.unwrap();
+        let rpc_addr = sandbox.rpc_addr.clone();
+        let account_id: AccountId = "alice.sandbox".parse().unwrap();
+
+        sandbox
+            .create_account(account_id.clone())
+            .send()
+            .await
+            .unwrap();
+        sandbox.fast_forward(100).await.unwrap();
+
+        sandbox.reset_state().await.unwrap();
+
+        assert_eq!(sandbox.rpc_addr, rpc_addr);
+        assert!(sandbox.get_block_height().await.unwrap() < 100);
+        assert!(sandbox.account_summary(&account_id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_export_home() {
+        let sandbox = Sandbox::start_sandbox().await.unwrap();
+        let account_id: AccountId = "alice.sandbox".parse().unwrap();
+        sandbox
+            .create_account(account_id.clone())
+            .send()
+            .await
+            .unwrap();
+
+        let dest = tempfile::tempdir().unwrap();
+        sandbox.export_home(dest.path()).await.unwrap();
+        for file in ["config.json", "genesis.json", "node_key.json", "README.md"] {
+            assert!(dest.path().join(file).exists(), "{file} is not exported");
+        }
+        // The instructions don't depend on where the home dir was exported to
+        let readme = std::fs::read_to_string(dest.path().join("README.md")).unwrap();
+        assert!(!readme.contains(dest.path().to_str().unwrap()));
+        assert!(dest.path().join("data").is_dir());
+
+        // The sandbox keeps running with its state
+        assert!(sandbox.account_exists(&account_id).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_rpc_port_range() {
+        let range = 34_000..=34_100;
+        let config = SandboxConfig {
+            rpc_port_range: Some(range.clone()),
+            ..Default::default()
+        };
+
+        let first = Sandbox::start_sandbox_with_config(config.clone())
+            .await
+            .unwrap();
+        let second = Sandbox::start_sandbox_with_config(config).await.unwrap();
+
+        assert!(range.contains(&first.rpc_port()));
+        assert!(range.contains(&second.rpc_port()));
+        assert_ne!(first.rpc_port(), second.rpc_port());
+    }
+
+    #[tokio::test]
+    async fn test_export_state() {
+        let sandbox = Sandbox::start_sandbox().await.unwrap();
+        let account_id: AccountId = "exported.sandbox".parse().unwrap();
+        sandbox
+            .create_account(account_id.clone())
+            .send()
+            .await
+            .unwrap();
+        // Let the account reach the final block
+        sandbox.fast_forward(5).await.unwrap();
+
+        let records = sandbox
+            .export_state(std::slice::from_ref(&account_id))
+            .await
+            .unwrap();
+        assert!(
+            records
+                .iter()
+                .any(|record| matches!(record, StateRecord::Account { .. }))
+        );
+        assert!(
+            records
+                .iter()
+                .any(|record| matches!(record, StateRecord::AccessKey { .. }))
+        );
+
+        let other = Sandbox::start_sandbox().await.unwrap();
+        records
+            .into_iter()
+            .fold(
+                other.patch_state(account_id.clone()),
+                PatchState::state_record,
+            )
+            .send()
+            .await
+            .unwrap();
+        other
+            .assert_account_matches(&account_id, &sandbox.rpc_addr)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_rpc_call() {
+        let sandbox = Sandbox::start_sandbox().await.unwrap();
+
+        let block = sandbox
+            .rpc_call("block", serde_json::json!({ "finality": "final" }))
+            .await
+            .unwrap();
+        assert!(block["header"]["height"].is_u64());
+
+        assert!(
+            sandbox
+                .rpc_call("no_such_method", serde_json::json!({}))
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_temp_dir_root() {
+        let root = tempfile::tempdir().unwrap();
+        let config = SandboxConfig {
+            temp_dir_root: Some(root.path().to_owned()),
+            ..Default::default()
+        };
+        let sandbox = Sandbox::start_sandbox_with_config(config).await.unwrap();
+
+        assert!(sandbox.home_dir.path().starts_with(root.path()));
+        assert!(sandbox.home_dir.path().join("data").exists());
+    }
+
+    #[test]
+    fn test_home_dir_root() {
+        assert_eq!(home_dir_root(&SandboxConfig::default()), None);
+
+        let root = PathBuf::from("/tmp/sandboxes");
+        let config = SandboxConfig {
+            temp_dir_root: Some(root.clone()),
+            in_memory_store: true,
+            ..Default::default()
+        };
+        assert_eq!(home_dir_root(&config), Some(root));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_in_memory_store() {
+        let sandbox = Sandbox::start_sandbox_with_config(SandboxConfig {
+            in_memory_store: true,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        assert!(sandbox.home_dir.path().starts_with(IN_MEMORY_DIR));
+        sandbox.fast_forward(1).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_wait_until_ready() {
+        let sandbox = Sandbox::start_sandbox().await.unwrap();
+        sandbox
+            .wait_until_ready(Instant::now() + Duration::from_secs(5))
+            .await
+            .unwrap();
+
+        let port = bind_port_guard(0)
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let unreachable = ConnectedSandbox::new(
+            format!("http://127.0.0.1:{port}"),
+            config::DEFAULT_RPC_TIMEOUT,
+            0,
+            ureq::Agent::new_with_defaults(),
+            config::NEARCORE_MAX_PAYLOAD_SIZE,
+        );
+        assert!(matches!(
+            unreachable.wait_until_ready(Instant::now()).await,
+            Err(SandboxError::TimeoutError)
+        ));
+
+        // Accepts the connections but never responds, so only the deadline stops the request
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let hanging = ConnectedSandbox::new(
+            format!("http://{}", listener.local_addr().unwrap()),
+            Duration::from_secs(60),
+            0,
+            ureq::Agent::new_with_defaults(),
+            config::NEARCORE_MAX_PAYLOAD_SIZE,
+        );
+        let started = Instant::now();
+        assert!(matches!(
+            hanging
+                .wait_until_ready(started + Duration::from_secs(1))
+                .await,
+            Err(SandboxError::TimeoutError)
+        ));
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_startup_retries() {
+        // Every configured attempt is made, so the last one succeeds
+        let timeouts = fault_injection::StartupTimeouts::new(2);
+        Sandbox::start_sandbox_with_config(SandboxConfig {
+            port_transfer_retries: Some(3),
+            forced_startup_timeouts: Some(timeouts.clone()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        assert_eq!(timeouts.remaining(), 0);
+
+        let timeouts = fault_injection::StartupTimeouts::new(4);
+        let result = Sandbox::start_sandbox_with_config(SandboxConfig {
+            port_transfer_retries: Some(3),
+            forced_startup_timeouts: Some(timeouts.clone()),
+            ..Default::default()
+        })
+        .await;
+        assert!(matches!(
+            result,
+            Err(SandboxError::SandboxStartupRetriesExhausted(3))
+        ));
+        assert_eq!(timeouts.remaining(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_env_overrides_log_vars() {
+        let config = SandboxConfig {
+            env: HashMap::from([("RUST_LOG".to_owned(), "info".to_owned())]),
+            capture_logs: true,
+            ..Default::default()
+        };
+        let sandbox = Sandbox::start_sandbox_with_config(config).await.unwrap();
+        sandbox.produce_blocks(2).await.unwrap();
+
+        assert!(sandbox.logs().contains("INFO"));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_neard_runs_in_home_dir() {
+        let sandbox = Sandbox::start_sandbox().await.unwrap();
+        let pid = sandbox.lock_process().child.id().unwrap();
+
+        let cwd = std::fs::read_link(format!("/proc/{pid}/cwd")).unwrap();
+        assert_eq!(cwd, sandbox.home_dir.path().canonicalize().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_start_with_bin_path() {
+        let bin_path = crate::install().unwrap();
+        let sandbox =
+            Sandbox::start_sandbox_with_config_and_bin_path(SandboxConfig::default(), bin_path)
+                .await
+                .unwrap();
+        sandbox.fast_forward(1).await.unwrap();
+
+        let missing = Sandbox::start_sandbox_with_config_and_bin_path(
+            SandboxConfig::default(),
+            PathBuf::from("/nonexistent/neard"),
+        )
+        .await;
+        assert!(matches!(missing, Err(SandboxError::BinaryError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_command_wrapper() {
+        let config = SandboxConfig {
+            command_wrapper: Some(vec!["env".to_owned(), "NEAR_WRAPPED=1".to_owned()]),
+            ..Default::default()
+        };
+        let sandbox = Sandbox::start_sandbox_with_config(config).await.unwrap();
+        sandbox.status().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_rpc_timeout() {
+        let config = SandboxConfig {
+            rpc_timeout: Some(Duration::from_secs(5)),
+            ..Default::default()
+        };
+        let mut sandbox = Sandbox::start_sandbox_with_config(config).await.unwrap();
+        assert_eq!(sandbox.rpc_timeout(), Duration::from_secs(5));
+        sandbox.status().await.unwrap();
+
+        sandbox.set_rpc_timeout(Duration::from_nanos(1));
+        assert!(sandbox.status().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_max_payload_size() {
+        let config = SandboxConfig {
+            max_payload_size: Some(64 * 1024 * 1024),
+            ..Default::default()
+        };
+        let sandbox = Sandbox::start_sandbox_with_config(config).await.unwrap();
+        assert_eq!(sandbox.max_payload_size(), 64 * 1024 * 1024);
+
+        let config = SandboxConfig {
+            max_payload_size: Some(64 * 1024 * 1024),
+            additional_config: Some(serde_json::json!({
+                "rpc": { "limits_config": { "json_payload_max_size": 1024 * 1024 } }
+            })),
+            ..Default::default()
+        };
+        assert_eq!(config::max_payload_size(&config), 1024 * 1024);
+    }
+
+    #[tokio::test]
+    async fn test_on_ready() {
+        let account_id: AccountId = "seeded.sandbox".parse().unwrap();
+        let seeded = account_id.clone();
+        let config = SandboxConfig {
+            on_ready: Some(config::OnReady::new(move |sandbox| {
+                let account_id = seeded.clone();
+                Box::pin(async move {
+                    sandbox.create_account(account_id).send().await?;
+                    Ok(())
+                })
+            })),
+            ..Default::default()
+        };
+        let sandbox = Sandbox::start_sandbox_with_config(config).await.unwrap();
+        assert!(sandbox.account_exists(&account_id).await.unwrap());
+
+        let config = SandboxConfig {
+            on_ready: Some(config::OnReady::new(|_| {
+                Box::pin(async { Err("fixture failed".into()) })
+            })),
+            ..Default::default()
+        };
+        let result = Sandbox::start_sandbox_with_config(config).await;
+        assert!(matches!(result, Err(SandboxError::SetupError(e)) if e == "fixture failed"));
+    }
+
+    #[tokio::test]
+    async fn test_runtime_limits() {
+        const GAS_LIMIT: u64 = 500_000_000_000_000;
+
+        let contract = config::GenesisAccount {
+            account_id: "contract.sandbox".parse().unwrap(),
+            ..Default::default()
+        }
+        .with_code(NOOP_CONTRACT.to_vec());
+        let config = SandboxConfig {
+            additional_accounts: vec![contract.clone()],
+            gas_limit: Some(GAS_LIMIT),
+            transaction_validity_period: Some(42),
+            ..Default::default()
+        };
+        let sandbox = Sandbox::start_sandbox_with_config(config.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            sandbox.protocol_config().await.unwrap().gas_limit,
+            GAS_LIMIT
+        );
+        let genesis = sandbox
+            .rpc_call("EXPERIMENTAL_genesis_config", serde_json::Value::Null)
+            .await
+            .unwrap();
+        assert_eq!(genesis["transaction_validity_period"], 42);
+        sandbox
+            .view_call(&contract.account_id, "new", b"")
+            .await
+            .unwrap();
+
+        // Loading the contract alone burns more gas than that
+        let sandbox = Sandbox::start_sandbox_with_config(SandboxConfig {
+            max_gas_burnt_view: Some(1),
+            ..config
+        })
+        .await
+        .unwrap();
+        assert!(
+            sandbox
+                .view_call(&contract.account_id, "new", b"")
+                .await
+                .is_err()
+        );
+    }
+
     #[cfg(feature = "__stress_test")]
     #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
     async fn test_multiple_sandboxes() {
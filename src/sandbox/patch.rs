@@ -1,8 +1,12 @@
+use base64::Engine;
 use near_account_id::AccountId;
 use near_token::NearToken;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-use crate::{Sandbox, config::DEFAULT_GENESIS_ACCOUNT_PUBLIC_KEY, error_kind::SandboxRpcError};
+use crate::{
+    BlockReference, ConnectedSandbox, config::DEFAULT_GENESIS_ACCOUNT_PUBLIC_KEY,
+    error_kind::SandboxRpcError, sandbox::query::QueryRequest,
+};
 
 /// Builder for specifying what data to fetch from an RPC endpoint
 #[derive(Clone, Copy, Default)]
@@ -57,22 +61,60 @@ impl FetchData {
 pub struct PatchState<'a> {
     pub destination_account: AccountId,
     pub state: Vec<StateRecord>,
-    pub sandbox: &'a Sandbox,
+    pub sandbox: &'a ConnectedSandbox,
     pub initial_balance: Option<NearToken>,
+    /// Block to fetch the data from. Defaults to [`BlockReference::Optimistic`]
+    pub fetch_block: BlockReference,
+    /// Extra headers attached to the requests to the RPC the data is fetched from
+    pub fetch_headers: Vec<(String, String)>,
 }
 
 impl<'a> PatchState<'a> {
     const EMPTY: Vec<serde_json::Value> = Vec::new();
 
-    pub const fn new(destination_account: AccountId, sandbox: &'a Sandbox) -> Self {
+    pub const fn new(destination_account: AccountId, sandbox: &'a ConnectedSandbox) -> Self {
         Self {
             state: vec![],
             destination_account,
             sandbox,
             initial_balance: None,
+            fetch_block: BlockReference::Optimistic,
+            fetch_headers: Vec::new(),
         }
     }
 
+    /// Set the block that [`Self::fetch_from`] and [`Self::fetch_from_account`] will query the data at.
+    ///
+    /// Optimistic data might come from a block that is not final yet, so re-importing the same account
+    /// could give a different result. Use [`BlockReference::Final`] or a specific final block
+    /// for reproducible fixtures.
+    pub fn fetch_at(mut self, block: BlockReference) -> Self {
+        self.fetch_block = block;
+        self
+    }
+
+    /// Attach a header to the requests made by [`Self::fetch_from`] and [`Self::fetch_from_account`].
+    ///
+    /// Useful for RPC providers that require an API key or a specific `User-Agent`:
+    ///
+    /// ```rust,no_run
+    /// # use near_sandbox::*;
+    /// # async fn example(sandbox: &Sandbox) -> Result<(), Box<dyn std::error::Error>> {
+    /// sandbox
+    ///     .patch_state("user.near".parse()?)
+    ///     .fetch_header("x-api-key", "<your api key>")
+    ///     .fetch_from("https://rpc.mainnet.fastnear.com", FetchData::ALL)
+    ///     .await?
+    ///     .send()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn fetch_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.fetch_headers.push((name.into(), value.into()));
+        self
+    }
+
     pub fn account(mut self, account: impl Serialize) -> Self {
         self.state.push(StateRecord::Account {
             account_id: self.destination_account.clone(),
@@ -92,25 +134,40 @@ impl<'a> PatchState<'a> {
         self.fetch_from_account(&account_id, rpc, fetch_data).await
     }
 
+    /// Fetch data of `account_id` from an RPC endpoint and store it under [`Self::destination_account`].
+    ///
+    /// The requested pieces are fetched concurrently, but the records are always added in the
+    /// same order: account, code, storage, access keys.
     pub async fn fetch_from_account(
         mut self,
         account_id: &AccountId,
         rpc: impl AsRef<str>,
         fetch_data: FetchData,
     ) -> Result<Self, SandboxRpcError> {
-        let rpc = rpc.as_ref().to_string();
-        if fetch_data.fetch_account {
-            self = self.fetch_account(account_id, rpc.clone()).await?;
-        }
-        if fetch_data.fetch_code {
-            self = self.fetch_code(account_id, rpc.clone()).await?;
-        }
-        if fetch_data.fetch_storage {
-            self = self.fetch_storage(account_id, rpc.clone()).await?;
-        }
-        if fetch_data.fetch_access_keys {
-            self = self.fetch_access_keys(account_id, rpc.clone()).await?;
-        }
+        let rpc = rpc.as_ref();
+        let (account, code, storage, access_keys) = tokio::try_join!(
+            fetch_if(
+                fetch_data.fetch_account,
+                self.fetch_account(account_id, rpc)
+            ),
+            fetch_if(fetch_data.fetch_code, self.fetch_code(account_id, rpc)),
+            fetch_if(
+                fetch_data.fetch_storage,
+                self.fetch_storage(account_id, rpc)
+            ),
+            fetch_if(
+                fetch_data.fetch_access_keys,
+                self.fetch_access_keys(account_id, rpc)
+            ),
+        )?;
+
+        self.state.extend(
+            account
+                .into_iter()
+                .chain(code)
+                .chain(storage)
+                .chain(access_keys),
+        );
         Ok(self)
     }
 
@@ -158,20 +215,34 @@ impl<'a> PatchState<'a> {
         self
     }
 
-    /// Adds [DEFAULT_GENESIS_ACCOUNT_PUBLIC_KEY] as FullAccess key to the account
+    /// Adds `public_key` as FullAccess key to the account, with `nonce` as the nonce of its last transaction.
     ///
-    /// You can get the private key from [crate::config::DEFAULT_GENESIS_ACCOUNT_PRIVATE_KEY] constant
-    pub fn with_default_access_key(mut self) -> Self {
-        self.state.push(StateRecord::AccessKey {
-            account_id: self.destination_account.clone(),
-            public_key_base64: DEFAULT_GENESIS_ACCOUNT_PUBLIC_KEY.to_owned(),
-            access_key: serde_json::json!({
-                "nonce": 0,
+    /// The next transaction signed with the key has to use a higher nonce.
+    pub fn full_access_key(self, public_key: String, nonce: u64) -> Self {
+        self.access_key(
+            public_key,
+            serde_json::json!({
+                "nonce": nonce,
                 "permission": "FullAccess"
             }),
-        });
+        )
+    }
 
-        self
+    /// Adds [DEFAULT_GENESIS_ACCOUNT_PUBLIC_KEY] as FullAccess key to the account
+    ///
+    /// You can get the private key from [crate::config::DEFAULT_GENESIS_ACCOUNT_PRIVATE_KEY] constant
+    pub fn with_default_access_key(self) -> Self {
+        self.full_access_key(DEFAULT_GENESIS_ACCOUNT_PUBLIC_KEY.to_owned(), 0)
+    }
+
+    /// Whether the collected records already have the access key, e.g. fetched from another RPC
+    pub(crate) fn has_access_key(&self, public_key: &str) -> bool {
+        self.state.iter().any(|record| match record {
+            StateRecord::AccessKey {
+                public_key_base64, ..
+            } => public_key_base64 == public_key,
+            _ => false,
+        })
     }
 
     pub fn received_data(mut self, data_id_hash: String, data_base64: Option<String>) -> Self {
@@ -195,6 +266,15 @@ impl<'a> PatchState<'a> {
         self
     }
 
+    /// Apply the collected records to the sandbox state.
+    ///
+    /// Records overwrite the existing state, so sending the same patch multiple times
+    /// yields the same state. If the error is [`SandboxRpcError::PatchPartiallyApplied`], the records
+    /// have already been applied once and `send` can be safely retried.
+    ///
+    /// Records are validated with [`StateRecord::validate`] before sending. If they don't fit into the
+    /// payload limit of the RPC ([`crate::SandboxConfig::max_payload_size`]), they are split into
+    /// multiple requests sent one after another.
     pub async fn send(self) -> Result<(), SandboxRpcError> {
         let records = if let Some(balance) = self.initial_balance {
             self.process_initial_balance(balance).await?
@@ -202,37 +282,46 @@ impl<'a> PatchState<'a> {
             self.state
         };
 
-        self.sandbox
-            .send_request(
-                &self.sandbox.rpc_addr,
-                serde_json::json!({
-                    "jsonrpc": "2.0",
-                    "id": "0",
-                    "method": "sandbox_patch_state",
-                    "params": {
-                        "records": records,
-                    },
-                }),
-            )
-            .await?;
+        for (index, record) in records.iter().enumerate() {
+            record
+                .validate()
+                .map_err(|reason| SandboxRpcError::InvalidStateRecord(index, reason))?;
+        }
+
+        let batches = batch_records(&records, self.sandbox.max_payload_size);
+        let batch_params: Vec<_> = batches
+            .iter()
+            .map(|batch| serde_json::json!({ "records": batch }))
+            .collect();
+
+        for (index, (params, batch)) in batch_params.iter().zip(&batches).enumerate() {
+            self.sandbox
+                .rpc_call("sandbox_patch_state", params.clone())
+                .await
+                .map_err(|e| {
+                    if index == 0 {
+                        e
+                    } else {
+                        SandboxRpcError::PatchPartiallyApplied(Box::new(e))
+                    }
+                })?;
+            self.sandbox
+                .track_accounts(batch.iter().filter_map(StateRecord::account_id));
+        }
 
         // NOTE: For some reason, patching anything with account/contract related items takes two patches
         // otherwise its super non-deterministic and mostly just fails to locate the account afterwards: ¯\_(ツ)_/¯
         // From: https://github.com/near/near-workspaces-rs/commit/2b72b9b8491c3140ff2d30b0c45d09b200cb027b
         // Also: https://github.com/near/near-workspaces-rs/blob/918f6deede97170a125c1fd1d80097685015ad2a/workspaces/src/rpc/patch.rs#L328
-        self.sandbox
-            .send_request(
-                &self.sandbox.rpc_addr,
-                serde_json::json!({
-                    "jsonrpc": "2.0",
-                    "id": "0",
-                    "method": "sandbox_patch_state",
-                    "params": {
-                        "records": records,
-                    },
-                }),
-            )
-            .await?;
+        //
+        // Both patches carry exactly the same records, which are upserts, so applying them more than once
+        // is a no-op. That's what makes retrying the whole `send` safe if the second patch fails.
+        for params in batch_params {
+            self.sandbox
+                .rpc_call("sandbox_patch_state", params)
+                .await
+                .map_err(|e| SandboxRpcError::PatchPartiallyApplied(Box::new(e)))?;
+        }
 
         Ok(())
     }
@@ -260,37 +349,20 @@ impl<'a> PatchState<'a> {
             // Fetch from sandbox and modify
             let mut account = self
                 .sandbox
-                .send_request(
-                    &self.sandbox.rpc_addr,
-                    serde_json::json!({
-                        "jsonrpc": "2.0",
-                        "id": "0",
-                        "method": "query",
-                        "params": {
-                            "finality": "optimistic",
-                            "request_type": "view_account",
-                            "account_id": self.destination_account
-                        }
-                    }),
-                )
+                .query(QueryRequest::ViewAccount {
+                    account_id: self.destination_account.clone(),
+                })
                 .await?;
 
-            if let Some(obj) = account
-                .get_mut("result")
-                .ok_or(SandboxRpcError::UnexpectedResponse)?
-                .as_object_mut()
-            {
-                obj["amount"] = serde_json::json!(balance.to_string());
+            if let Some(obj) = account.as_object_mut() {
+                obj["amount"] = serde_json::json!(balance);
             }
 
             records.insert(
                 0,
                 StateRecord::Account {
                     account_id: self.destination_account.clone(),
-                    account: account
-                        .get_mut("result")
-                        .ok_or(SandboxRpcError::UnexpectedResponse)?
-                        .clone(),
+                    account,
                 },
             );
         }
@@ -298,165 +370,156 @@ impl<'a> PatchState<'a> {
         Ok(records)
     }
 
+    /// Send the `query` request to `from_rpc`, pinned to [`Self::fetch_block`]
+    async fn fetch_query(
+        &self,
+        from_rpc: &str,
+        request: QueryRequest,
+    ) -> Result<serde_json::Value, SandboxRpcError> {
+        self.sandbox
+            .query_rpc(from_rpc, &self.fetch_headers, &request, &self.fetch_block)
+            .await
+    }
+
     async fn fetch_account(
-        self,
+        &self,
         account_id: &AccountId,
-        from_rpc: String,
-    ) -> Result<PatchState<'a>, SandboxRpcError> {
+        from_rpc: &str,
+    ) -> Result<Vec<StateRecord>, SandboxRpcError> {
         let account = self
-            .sandbox
-            .send_request(
+            .fetch_query(
                 from_rpc,
-                serde_json::json!({
-                    "jsonrpc": "2.0",
-                    "id": "0",
-                    "method": "query",
-                    "params": {
-                        "finality": "optimistic",
-                        "request_type": "view_account",
-                        "account_id": account_id
-                    }
-                }),
+                QueryRequest::ViewAccount {
+                    account_id: account_id.clone(),
+                },
             )
             .await?;
 
-        Ok(self.account(
-            account
-                .get("result")
-                .ok_or(SandboxRpcError::UnexpectedResponse)?,
-        ))
+        Ok(vec![StateRecord::Account {
+            account_id: self.destination_account.clone(),
+            account,
+        }])
     }
 
     async fn fetch_storage(
-        self,
+        &self,
         account_id: &AccountId,
-        from_rpc: String,
-    ) -> Result<PatchState<'a>, SandboxRpcError> {
+        from_rpc: &str,
+    ) -> Result<Vec<StateRecord>, SandboxRpcError> {
         let storage = self
-            .sandbox
-            .send_request(
+            .fetch_query(
                 from_rpc,
-                serde_json::json!({
-                    "jsonrpc": "2.0",
-                    "id": "0",
-                    "method": "query",
-                    "params": {
-                        "finality": "optimistic",
-                        "request_type": "view_state",
-                        "account_id": account_id,
-                        "include_proof": false,
-                        "prefix_base64": "",
-                    }
-                }),
+                QueryRequest::ViewState {
+                    account_id: account_id.clone(),
+                    prefix: Vec::new(),
+                    include_proof: false,
+                },
             )
-            .await?;
+            .await
+            .map_err(|e| match e {
+                SandboxRpcError::SandboxRpcError(e) if e.contains("TOO_LARGE_CONTRACT_STATE") => {
+                    SandboxRpcError::ContractStateTooLarge(account_id.to_string())
+                }
+                e => e,
+            })?;
 
         let default_entry = Self::EMPTY;
         let entries = storage
-            .get("result")
-            .ok_or(SandboxRpcError::UnexpectedResponse)?
             .get("values")
             .ok_or(SandboxRpcError::UnexpectedResponse)?
             .as_array()
             .unwrap_or(&default_entry)
             .iter()
             .flat_map(|state| {
-                Some((
-                    state.get("key")?.as_str()?.to_owned(),
-                    state.get("value")?.as_str()?.to_owned(),
-                ))
+                Some(StateRecord::Data {
+                    account_id: self.destination_account.clone(),
+                    data_key_base64: state.get("key")?.as_str()?.to_owned(),
+                    value_base64: state.get("value")?.as_str()?.to_owned(),
+                })
             });
 
-        Ok(self.storage_entries(entries))
+        Ok(entries.collect())
     }
 
     async fn fetch_code(
-        self,
+        &self,
         account_id: &AccountId,
-        from_rpc: String,
-    ) -> Result<PatchState<'a>, SandboxRpcError> {
-        let code_response = self
-            .sandbox
-            .send_request(
+        from_rpc: &str,
+    ) -> Result<Vec<StateRecord>, SandboxRpcError> {
+        let code = self
+            .fetch_query(
                 from_rpc,
-                serde_json::json!({
-                    "jsonrpc": "2.0",
-                    "id": "0",
-                    "method": "query",
-                    "params": {
-                        "finality": "optimistic",
-                        "request_type": "view_code",
-                        "account_id": account_id,
-                    }
-                }),
+                QueryRequest::ViewCode {
+                    account_id: account_id.clone(),
+                },
             )
             .await?;
 
-        let code_base64 = code_response
-            .get("result")
-            .ok_or(SandboxRpcError::UnexpectedResponse)?
+        let code_base64 = code
             .get("code_base64")
             .ok_or(SandboxRpcError::UnexpectedResponse)?
             .as_str()
             .unwrap_or_default()
             .to_owned();
 
-        Ok(self.code(code_base64))
+        Ok(vec![StateRecord::Contract {
+            account_id: self.destination_account.clone(),
+            code_base64,
+        }])
     }
 
     async fn fetch_access_keys(
-        mut self,
+        &self,
         account_id: &AccountId,
-        from_rpc: String,
-    ) -> Result<PatchState<'a>, SandboxRpcError> {
+        from_rpc: &str,
+    ) -> Result<Vec<StateRecord>, SandboxRpcError> {
         let access_keys = self
-            .sandbox
-            .send_request(
+            .fetch_query(
                 from_rpc,
-                serde_json::json!({
-                    "jsonrpc": "2.0",
-                    "id": "0",
-                    "method": "query",
-                    "params": {
-                        "finality": "optimistic",
-                        "request_type": "view_access_key_list",
-                        "account_id": account_id,
-                    }
-                }),
+                QueryRequest::ViewAccessKeyList {
+                    account_id: account_id.clone(),
+                },
             )
             .await?;
 
-        for access_key in access_keys
-            .get("result")
-            .ok_or(SandboxRpcError::UnexpectedResponse)?
+        access_keys
             .get("keys")
             .ok_or(SandboxRpcError::UnexpectedResponse)?
             .as_array()
             .unwrap_or(&Self::EMPTY)
-        {
-            self = self.access_key(
-                access_key
-                    .get("public_key")
-                    .ok_or(SandboxRpcError::UnexpectedResponse)?
-                    .as_str()
-                    .unwrap_or_default()
-                    .to_owned(),
-                access_key
-                    .get("access_key")
-                    .ok_or(SandboxRpcError::UnexpectedResponse)?
-                    .clone(),
-            );
-        }
-
-        Ok(self)
+            .iter()
+            .map(|access_key| {
+                Ok(StateRecord::AccessKey {
+                    account_id: self.destination_account.clone(),
+                    public_key_base64: access_key
+                        .get("public_key")
+                        .ok_or(SandboxRpcError::UnexpectedResponse)?
+                        .as_str()
+                        .unwrap_or_default()
+                        .to_owned(),
+                    access_key: access_key
+                        .get("access_key")
+                        .ok_or(SandboxRpcError::UnexpectedResponse)?
+                        .clone(),
+                })
+            })
+            .collect()
     }
 }
 
+/// Run the `fetch` only if the data was requested
+async fn fetch_if(
+    enabled: bool,
+    fetch: impl Future<Output = Result<Vec<StateRecord>, SandboxRpcError>>,
+) -> Result<Vec<StateRecord>, SandboxRpcError> {
+    if enabled { fetch.await } else { Ok(Vec::new()) }
+}
+
 /// We don't want to introduce extra dependencies to the crate so we use serde_json::Value
 /// to represent more complex types.
 ///
 /// Though we still want to have at least some type safety.
-#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum StateRecord {
     Account {
         account_id: AccountId,
@@ -491,11 +554,147 @@ pub enum StateRecord {
     DelayedReceipt(serde_json::Value),
 }
 
+impl StateRecord {
+    /// Account the record belongs to, `None` for the receipts
+    pub const fn account_id(&self) -> Option<&AccountId> {
+        match self {
+            Self::Account { account_id, .. }
+            | Self::Data { account_id, .. }
+            | Self::Contract { account_id, .. }
+            | Self::AccessKey { account_id, .. }
+            | Self::ReceivedData { account_id, .. } => Some(account_id),
+            Self::PostponedReceipt(_) | Self::DelayedReceipt(_) => None,
+        }
+    }
+
+    /// Catch the common mistakes in the record before it's sent to the sandbox.
+    ///
+    /// Checks that base64 fields decode, public keys have a key type prefix and that required
+    /// numeric account and access key fields are present. Receipts are passed as is.
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            Self::Account { account, .. } => {
+                for field in ["amount", "locked"] {
+                    account
+                        .get(field)
+                        .and_then(|v| v.as_str())
+                        .and_then(|v| v.parse::<u128>().ok())
+                        .ok_or_else(|| {
+                            format!("account `{field}` must be a yoctoNEAR amount string")
+                        })?;
+                }
+                account
+                    .get("storage_usage")
+                    .and_then(|v| v.as_u64())
+                    .ok_or("account `storage_usage` must be an unsigned integer")?;
+                account
+                    .get("code_hash")
+                    .and_then(|v| v.as_str())
+                    .ok_or("account `code_hash` must be a string")?;
+            }
+            Self::Data {
+                data_key_base64,
+                value_base64,
+                ..
+            } => {
+                validate_base64("data_key", data_key_base64)?;
+                validate_base64("value", value_base64)?;
+            }
+            Self::Contract { code_base64, .. } => validate_base64("code", code_base64)?,
+            Self::AccessKey {
+                public_key_base64,
+                access_key,
+                ..
+            } => {
+                if !public_key_base64.starts_with("ed25519:")
+                    && !public_key_base64.starts_with("secp256k1:")
+                {
+                    return Err(format!(
+                        "public key `{public_key_base64}` must start with `ed25519:` or `secp256k1:`"
+                    ));
+                }
+                access_key
+                    .get("nonce")
+                    .and_then(|v| v.as_u64())
+                    .ok_or("access key `nonce` must be an unsigned integer")?;
+                access_key
+                    .get("permission")
+                    .ok_or("access key `permission` is missing")?;
+            }
+            Self::ReceivedData { data_base64, .. } => {
+                if let Some(data_base64) = data_base64 {
+                    validate_base64("data", data_base64)?;
+                }
+            }
+            Self::PostponedReceipt(_) | Self::DelayedReceipt(_) => {}
+        }
+
+        Ok(())
+    }
+}
+
+/// Split the records into batches whose `sandbox_patch_state` request fits into `max_payload_size` bytes.
+///
+/// A record that doesn't fit on its own is sent in a batch by itself, so the RPC reports the error.
+fn batch_records(records: &[StateRecord], max_payload_size: usize) -> Vec<&[StateRecord]> {
+    // Room for the JSON RPC envelope around the records
+    const REQUEST_OVERHEAD: usize = 1024;
+    let max_batch_size = max_payload_size.saturating_sub(REQUEST_OVERHEAD);
+
+    let mut batches = vec![];
+    let (mut start, mut batch_size) = (0, 0);
+    for (index, record) in records.iter().enumerate() {
+        // One more byte for the comma separating the records
+        let record_size = serde_json::to_vec(record).map_or(0, |record| record.len()) + 1;
+        if index > start && batch_size + record_size > max_batch_size {
+            batches.push(&records[start..index]);
+            (start, batch_size) = (index, 0);
+        }
+        batch_size += record_size;
+    }
+    if start < records.len() || records.is_empty() {
+        batches.push(&records[start..]);
+    }
+
+    batches
+}
+
+fn validate_base64(field: &str, value: &str) -> Result<(), String> {
+    base64::engine::general_purpose::STANDARD
+        .decode(value)
+        .map(|_| ())
+        .map_err(|e| format!("`{field}` is not valid base64: {e}"))
+}
+
 #[cfg(test)]
 mod tests {
+    use super::{StateRecord, batch_records};
     use crate::{FetchData, Sandbox};
     use near_api::{Account, AccountId, Contract, NearToken, NetworkConfig, RPCEndpoint};
 
+    #[test]
+    fn test_batch_records() {
+        let record = StateRecord::Data {
+            account_id: "alice.sandbox".parse().unwrap(),
+            data_key_base64: "a2V5".to_owned(),
+            value_base64: "A".repeat(1000),
+        };
+        let records = vec![record; 10];
+
+        assert_eq!(batch_records(&records, usize::MAX).len(), 1);
+        assert_eq!(batch_records(&[], 2048).len(), 1);
+
+        // Room for 3 records per request
+        let batches = batch_records(&records, 1024 + 3 * 1100);
+        assert_eq!(
+            batches.iter().map(|batch| batch.len()).collect::<Vec<_>>(),
+            [3, 3, 3, 1]
+        );
+
+        // Records bigger than the limit are sent one by one
+        assert_eq!(batch_records(&records, 1024).len(), 10);
+    }
+
     #[tokio::test]
     async fn test_patch_state() {
         let sandbox = Sandbox::start_sandbox().await.unwrap();
@@ -615,4 +814,44 @@ mod tests {
 
         println!("{:#?}", stats);
     }
+
+    #[test]
+    fn test_state_record_validation() {
+        let account_id: AccountId = "test.near".parse().unwrap();
+
+        let valid = StateRecord::Account {
+            account_id: account_id.clone(),
+            account: serde_json::json!({
+                "amount": NearToken::from_near(1),
+                "locked": "0",
+                "code_hash": "11111111111111111111111111111111",
+                "storage_usage": 182,
+            }),
+        };
+        assert!(valid.validate().is_ok());
+
+        let human_amount = StateRecord::Account {
+            account_id: account_id.clone(),
+            account: serde_json::json!({
+                "amount": "1 NEAR",
+                "locked": "0",
+                "code_hash": "11111111111111111111111111111111",
+                "storage_usage": 182,
+            }),
+        };
+        assert!(human_amount.validate().is_err());
+
+        let bad_code = StateRecord::Contract {
+            account_id: account_id.clone(),
+            code_base64: "not base64!".to_owned(),
+        };
+        assert!(bad_code.validate().is_err());
+
+        let bad_key = StateRecord::AccessKey {
+            account_id,
+            public_key_base64: "6E8sCci9badyRkXb3JoRpBj5p8C6Tw41ELDZoiihKEtp".to_owned(),
+            access_key: serde_json::json!({ "nonce": 0, "permission": "FullAccess" }),
+        };
+        assert!(bad_key.validate().is_err());
+    }
 }
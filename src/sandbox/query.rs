@@ -0,0 +1,1136 @@
+//! Typed helpers for reading chain data from the sandbox.
+//
+// NOTE: Same as with `config.rs`, we don't try to mirror nearcore views here. Only the fields
+// that are commonly asserted on in tests are parsed, everything else is ignored.
+
+use std::time::Duration;
+
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
+use near_account_id::AccountId;
+use near_token::NearToken;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::ConnectedSandbox;
+use crate::error_kind::SandboxRpcError;
+
+/// Reference to a block used by block-related queries
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlockReference {
+    /// Latest block that reached finality
+    Final,
+    /// Latest block, that might not be final yet
+    Optimistic,
+    /// Block at the given height
+    Height(u64),
+    /// Block with the given base58 encoded hash
+    Hash(String),
+}
+
+impl BlockReference {
+    pub(crate) fn to_params(&self) -> serde_json::Value {
+        match self {
+            Self::Final => serde_json::json!({ "finality": "final" }),
+            Self::Optimistic => serde_json::json!({ "finality": "optimistic" }),
+            Self::Height(height) => serde_json::json!({ "block_id": height }),
+            Self::Hash(hash) => serde_json::json!({ "block_id": hash }),
+        }
+    }
+}
+
+impl From<u64> for BlockReference {
+    fn from(height: u64) -> Self {
+        Self::Height(height)
+    }
+}
+
+/// Request of the `query` RPC method, see [`ConnectedSandbox::query`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueryRequest {
+    ViewAccount {
+        account_id: AccountId,
+    },
+    ViewCode {
+        account_id: AccountId,
+    },
+    /// Contract storage entries with keys starting with `prefix`, all of them if it's empty
+    ViewState {
+        account_id: AccountId,
+        prefix: Vec<u8>,
+        include_proof: bool,
+    },
+    ViewAccessKey {
+        account_id: AccountId,
+        public_key: String,
+    },
+    ViewAccessKeyList {
+        account_id: AccountId,
+    },
+    /// Call of a view method, `args` are passed to the method as is
+    CallFunction {
+        account_id: AccountId,
+        method_name: String,
+        args: Vec<u8>,
+    },
+}
+
+impl QueryRequest {
+    /// Query params with the block the query is made at
+    pub(crate) fn params_at(&self, block: &BlockReference) -> serde_json::Value {
+        let mut params = self.to_params();
+        json_patch::merge(&mut params, &block.to_params());
+        params
+    }
+
+    /// Query params without the block reference
+    pub(crate) fn to_params(&self) -> serde_json::Value {
+        match self {
+            Self::ViewAccount { account_id } => serde_json::json!({
+                "request_type": "view_account",
+                "account_id": account_id,
+            }),
+            Self::ViewCode { account_id } => serde_json::json!({
+                "request_type": "view_code",
+                "account_id": account_id,
+            }),
+            Self::ViewState {
+                account_id,
+                prefix,
+                include_proof,
+            } => serde_json::json!({
+                "request_type": "view_state",
+                "account_id": account_id,
+                "prefix_base64": BASE64_STANDARD.encode(prefix),
+                "include_proof": include_proof,
+            }),
+            Self::ViewAccessKey {
+                account_id,
+                public_key,
+            } => serde_json::json!({
+                "request_type": "view_access_key",
+                "account_id": account_id,
+                "public_key": public_key,
+            }),
+            Self::ViewAccessKeyList { account_id } => serde_json::json!({
+                "request_type": "view_access_key_list",
+                "account_id": account_id,
+            }),
+            Self::CallFunction {
+                account_id,
+                method_name,
+                args,
+            } => serde_json::json!({
+                "request_type": "call_function",
+                "account_id": account_id,
+                "method_name": method_name,
+                "args_base64": BASE64_STANDARD.encode(args),
+            }),
+        }
+    }
+}
+
+/// Block data returned by the `block` RPC method
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub height: u64,
+    pub hash: String,
+    pub prev_hash: String,
+    /// Block timestamp in nanoseconds
+    pub timestamp: u64,
+    /// Block producer of the block
+    pub author: AccountId,
+    pub chunks: Vec<ChunkHeader>,
+}
+
+/// Chunk header included in the [`Block`]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ChunkHeader {
+    pub chunk_hash: String,
+    pub shard_id: u64,
+    pub height_created: u64,
+    pub height_included: u64,
+    pub gas_used: u64,
+    pub gas_limit: u64,
+}
+
+/// Account data returned by the `view_account` query
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct AccountSummary {
+    pub amount: NearToken,
+    pub locked: NearToken,
+    /// Base58 encoded hash of the deployed contract. `11111111111111111111111111111111` if there is no contract
+    pub code_hash: String,
+    pub storage_usage: u64,
+}
+
+/// Access key data returned by the `view_access_key` query
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct AccessKeyView {
+    /// Nonce of the last transaction signed with the key, the next one has to use a higher nonce
+    pub nonce: u64,
+    pub permission: AccessKeyPermission,
+    /// Base58 encoded hash of the block the key was read at, can be used as the transaction block hash
+    pub block_hash: String,
+    pub block_height: u64,
+}
+
+/// Latest block known to the node, the `sync_info` of the `status` RPC method
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct SyncInfo {
+    pub latest_block_height: u64,
+    /// Base58 encoded hash of the latest block
+    pub latest_block_hash: String,
+    /// Timestamp of the latest block in RFC 3339 format
+    pub latest_block_time: String,
+    pub latest_state_root: String,
+    /// Whether the node is still catching up with the chain, never the case for a single-node sandbox
+    pub syncing: bool,
+}
+
+/// Permission of the [`AccessKeyView`]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub enum AccessKeyPermission {
+    FullAccess,
+    FunctionCall {
+        /// Balance the key can spend on gas, unlimited if `None`
+        allowance: Option<NearToken>,
+        receiver_id: AccountId,
+        /// Methods the key can call, any method if empty
+        method_names: Vec<String>,
+    },
+}
+
+/// Version of the neard binary serving the RPC, taken from the `status` RPC method
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeVersion {
+    /// Release version, e.g. `2.10.7`
+    pub version: String,
+    /// Build of the binary, usually the git describe of nearcore
+    pub build: String,
+    /// Version of rustc the binary was compiled with
+    pub rustc_version: String,
+    /// Protocol version the node is running
+    pub protocol_version: u32,
+}
+
+#[derive(Deserialize)]
+struct StatusVersionView {
+    version: String,
+    build: String,
+    #[serde(default)]
+    rustc_version: String,
+}
+
+/// Protocol parameters returned by the `EXPERIMENTAL_protocol_config` RPC method
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProtocolConfig {
+    pub protocol_version: u32,
+    /// Number of blocks in an epoch
+    pub epoch_length: u64,
+    /// Maximum gas that can be spent in a single chunk
+    pub gas_limit: u64,
+    /// Minimum gas price in yoctoNEAR per gas unit
+    pub min_gas_price: u128,
+    /// Maximum gas price in yoctoNEAR per gas unit
+    pub max_gas_price: u128,
+    /// Balance locked per byte of the account storage
+    pub storage_amount_per_byte: NearToken,
+    /// Maximum gas a single function call can burn
+    pub max_gas_burnt: u64,
+    /// Fees of the actions and the data receipts, as returned by the RPC (`runtime_config.transaction_costs`)
+    pub transaction_costs: serde_json::Value,
+}
+
+impl ProtocolConfig {
+    fn from_json(config: &serde_json::Value) -> Option<Self> {
+        // NOTE: Balances are u128 serialized as strings, gas values might be either numbers or strings
+        // depending on the nearcore version
+        let number = |value: &serde_json::Value| {
+            value
+                .as_u64()
+                .map(u128::from)
+                .or_else(|| value.as_str()?.parse().ok())
+        };
+        let runtime_config = &config["runtime_config"];
+
+        Some(Self {
+            protocol_version: config["protocol_version"].as_u64()?.try_into().ok()?,
+            epoch_length: config["epoch_length"].as_u64()?,
+            gas_limit: number(&config["gas_limit"])?.try_into().ok()?,
+            min_gas_price: number(&config["min_gas_price"])?,
+            max_gas_price: number(&config["max_gas_price"])?,
+            storage_amount_per_byte: NearToken::from_yoctonear(number(
+                &runtime_config["storage_amount_per_byte"],
+            )?),
+            max_gas_burnt: number(&runtime_config["wasm_config"]["limit_config"]["max_gas_burnt"])?
+                .try_into()
+                .ok()?,
+            transaction_costs: runtime_config["transaction_costs"].clone(),
+        })
+    }
+}
+
+/// Receipts that are still to be processed as of the latest block
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PendingReceipts {
+    /// Height of the block the data was taken at
+    pub block_height: u64,
+    /// Number of receipts included in the chunks of the block, i.e. produced by the previous block
+    pub in_flight: usize,
+    /// Gas attached to the receipts in the delayed receipts queues of all the shards
+    pub delayed_receipts_gas: u128,
+    /// Gas attached to the receipts buffered for the other shards
+    pub buffered_receipts_gas: u128,
+}
+
+impl PendingReceipts {
+    pub const fn is_drained(&self) -> bool {
+        self.in_flight == 0 && self.delayed_receipts_gas == 0 && self.buffered_receipts_gas == 0
+    }
+}
+
+#[derive(Deserialize)]
+struct BlockView {
+    author: AccountId,
+    header: BlockHeaderView,
+    chunks: Vec<ChunkHeader>,
+}
+
+#[derive(Deserialize)]
+struct BlockHeaderView {
+    height: u64,
+    hash: String,
+    prev_hash: String,
+    timestamp: u64,
+}
+
+impl From<BlockView> for Block {
+    fn from(view: BlockView) -> Self {
+        Self {
+            height: view.header.height,
+            hash: view.header.hash,
+            prev_hash: view.header.prev_hash,
+            timestamp: view.header.timestamp,
+            author: view.author,
+            chunks: view.chunks,
+        }
+    }
+}
+
+impl ConnectedSandbox {
+    /// Fetch a block from the sandbox.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use near_sandbox::*;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let sandbox = Sandbox::start_sandbox().await?;
+    /// sandbox.fast_forward(10).await?;
+    ///
+    /// let block = sandbox.block(BlockReference::Final).await?;
+    /// println!("block #{} produced by {} at {}", block.height, block.author, block.timestamp);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn block(&self, reference: BlockReference) -> Result<Block, SandboxRpcError> {
+        let block: BlockView =
+            serde_json::from_value(self.rpc_call("block", reference.to_params()).await?)
+                .map_err(|_| SandboxRpcError::UnexpectedResponse)?;
+
+        Ok(block.into())
+    }
+
+    /// Timestamp of the latest block in nanoseconds, i.e. what `env::block_timestamp()` returns to contracts.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use near_sandbox::*;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let sandbox = Sandbox::start_sandbox().await?;
+    /// let vesting_start = sandbox.block_timestamp().await?;
+    /// sandbox.fast_forward(1000).await?;
+    /// println!("{}ns passed", sandbox.block_timestamp().await? - vesting_start);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn block_timestamp(&self) -> Result<u64, SandboxRpcError> {
+        Ok(self.block(BlockReference::Optimistic).await?.timestamp)
+    }
+
+    /// Run a `query` RPC request at the optimistic block and return its `result` as is.
+    ///
+    /// Lower-level than the typed helpers like [`Self::account_summary`] or [`Self::view_call`],
+    /// which are built on top of it.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use near_sandbox::*;
+    /// use near_sandbox::sandbox::query::QueryRequest;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let sandbox = Sandbox::start_sandbox().await?;
+    /// let keys = sandbox
+    ///     .query(QueryRequest::ViewAccessKeyList {
+    ///         account_id: "sandbox".parse()?,
+    ///     })
+    ///     .await?;
+    /// println!("{}", keys["keys"]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn query(&self, request: QueryRequest) -> Result<serde_json::Value, SandboxRpcError> {
+        self.rpc_call("query", request.params_at(&BlockReference::Optimistic))
+            .await
+    }
+
+    /// Send the `query` request to the RPC of any network at `block` and return the `result` of the response.
+    ///
+    /// Used to read the state of the other networks, e.g. the one an account is imported from.
+    pub(crate) async fn query_rpc(
+        &self,
+        rpc: &str,
+        headers: &[(String, String)],
+        request: &QueryRequest,
+        block: &BlockReference,
+    ) -> Result<serde_json::Value, SandboxRpcError> {
+        let mut response = self
+            .send_request_with_headers(
+                rpc,
+                headers,
+                serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "id": "0",
+                    "method": "query",
+                    "params": request.params_at(block),
+                }),
+            )
+            .await?;
+
+        response
+            .get_mut("result")
+            .map(serde_json::Value::take)
+            .ok_or(SandboxRpcError::UnexpectedResponse)
+    }
+
+    /// Fetch balance, code hash and storage usage of the account.
+    ///
+    /// Comparing code hash and storage usage is a cheap way to check that an imported contract
+    /// matches the source without fetching the whole code.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use near_sandbox::*;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let sandbox = Sandbox::start_sandbox().await?;
+    /// let summary = sandbox.account_summary(&"sandbox".parse()?).await?;
+    /// println!("{} uses {} bytes", summary.code_hash, summary.storage_usage);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn account_summary(
+        &self,
+        account_id: &AccountId,
+    ) -> Result<AccountSummary, SandboxRpcError> {
+        let account = self
+            .query(QueryRequest::ViewAccount {
+                account_id: account_id.clone(),
+            })
+            .await?;
+
+        serde_json::from_value(account).map_err(|_| SandboxRpcError::UnexpectedResponse)
+    }
+
+    /// Wasm code deployed to the account, e.g. to check that it matches a local build.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use near_sandbox::*;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let sandbox = Sandbox::start_sandbox().await?;
+    /// let code = sandbox.contract_code(&"contract.sandbox".parse()?).await?;
+    /// assert_eq!(code, std::fs::read("res/contract.wasm")?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn contract_code(&self, account_id: &AccountId) -> Result<Vec<u8>, SandboxRpcError> {
+        let code = self
+            .query(QueryRequest::ViewCode {
+                account_id: account_id.clone(),
+            })
+            .await?;
+
+        code.get("code_base64")
+            .and_then(serde_json::Value::as_str)
+            .and_then(|code| BASE64_STANDARD.decode(code).ok())
+            .ok_or(SandboxRpcError::UnexpectedResponse)
+    }
+
+    /// Access key `public_key` of the account, e.g. to get the nonce for a manually built transaction.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use near_sandbox::*;
+    /// use near_sandbox::config::DEFAULT_GENESIS_ACCOUNT_PUBLIC_KEY;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let sandbox = Sandbox::start_sandbox().await?;
+    /// let key = sandbox
+    ///     .access_key(&"sandbox".parse()?, DEFAULT_GENESIS_ACCOUNT_PUBLIC_KEY)
+    ///     .await?;
+    /// println!("next nonce: {}", key.nonce + 1);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn access_key(
+        &self,
+        account_id: &AccountId,
+        public_key: &str,
+    ) -> Result<AccessKeyView, SandboxRpcError> {
+        let access_key = self
+            .query(QueryRequest::ViewAccessKey {
+                account_id: account_id.clone(),
+                public_key: public_key.to_owned(),
+            })
+            .await?;
+
+        serde_json::from_value(access_key).map_err(|_| SandboxRpcError::UnexpectedResponse)
+    }
+
+    /// Nonce to sign the next transaction of the access key `public_key` with, i.e. its current nonce + 1.
+    ///
+    /// Fetch it once to build a batch of transactions offline, incrementing the nonce for each of them.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use near_sandbox::*;
+    /// use near_sandbox::config::DEFAULT_GENESIS_ACCOUNT_PUBLIC_KEY;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let sandbox = Sandbox::start_sandbox().await?;
+    /// let first_nonce = sandbox
+    ///     .next_nonce(&"sandbox".parse()?, DEFAULT_GENESIS_ACCOUNT_PUBLIC_KEY)
+    ///     .await?;
+    /// let nonces: Vec<u64> = (first_nonce..first_nonce + 100).collect();
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn next_nonce(
+        &self,
+        account_id: &AccountId,
+        public_key: &str,
+    ) -> Result<u64, SandboxRpcError> {
+        Ok(self.access_key(account_id, public_key).await?.nonce + 1)
+    }
+
+    /// Liquid balance of the account, i.e. the `amount` of [`Self::account_summary`].
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use near_sandbox::*;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let sandbox = Sandbox::start_sandbox().await?;
+    /// println!("{}", sandbox.account_balance(&"sandbox".parse()?).await?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn account_balance(
+        &self,
+        account_id: &AccountId,
+    ) -> Result<NearToken, SandboxRpcError> {
+        Ok(self.account_summary(account_id).await?.amount)
+    }
+
+    /// Version and build of neard, useful to log with the test failures.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use near_sandbox::*;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let sandbox = Sandbox::start_sandbox().await?;
+    /// let node = sandbox.node_version().await?;
+    /// println!("neard {} ({}), protocol {}", node.version, node.build, node.protocol_version);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn node_version(&self) -> Result<NodeVersion, SandboxRpcError> {
+        let status = self.status().await?;
+
+        let version: StatusVersionView = serde_json::from_value(
+            status
+                .get("version")
+                .ok_or(SandboxRpcError::UnexpectedResponse)?
+                .clone(),
+        )
+        .map_err(|_| SandboxRpcError::UnexpectedResponse)?;
+        let protocol_version = status
+            .get("protocol_version")
+            .and_then(|version| version.as_u64())
+            .and_then(|version| u32::try_from(version).ok())
+            .ok_or(SandboxRpcError::UnexpectedResponse)?;
+
+        Ok(NodeVersion {
+            version: version.version,
+            build: version.build,
+            rustc_version: version.rustc_version,
+            protocol_version,
+        })
+    }
+
+    /// Call a view method of the contract and return the raw bytes it returned.
+    ///
+    /// `args` are passed to the method as is, e.g. JSON or borsh encoded.
+    pub async fn view_call(
+        &self,
+        contract_id: &AccountId,
+        method: &str,
+        args: &[u8],
+    ) -> Result<Vec<u8>, SandboxRpcError> {
+        let mut response = self
+            .query(QueryRequest::CallFunction {
+                account_id: contract_id.clone(),
+                method_name: method.to_owned(),
+                args: args.to_vec(),
+            })
+            .await?;
+
+        serde_json::from_value(
+            response
+                .get_mut("result")
+                .map(serde_json::Value::take)
+                .ok_or(SandboxRpcError::UnexpectedResponse)?,
+        )
+        .map_err(|_| SandboxRpcError::UnexpectedResponse)
+    }
+
+    /// Call a view method of the contract with JSON `args` and deserialize the returned JSON into `T`.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use near_sandbox::*;
+    ///
+    /// #[derive(serde::Deserialize)]
+    /// struct Greeting {
+    ///     text: String,
+    /// }
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let sandbox = Sandbox::start_sandbox().await?;
+    /// let greeting: Greeting = sandbox
+    ///     .view_call_json(&"greeter.sandbox".parse()?, "get_greeting", serde_json::json!({}))
+    ///     .await?;
+    /// println!("{}", greeting.text);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn view_call_json<T: DeserializeOwned>(
+        &self,
+        contract_id: &AccountId,
+        method: &str,
+        args: impl Serialize,
+    ) -> Result<T, SandboxRpcError> {
+        let args = serde_json::to_vec(&args)
+            .map_err(|e| SandboxRpcError::ArgsSerializeError(e.to_string()))?;
+        let result = self.view_call(contract_id, method, &args).await?;
+
+        serde_json::from_slice(&result).map_err(|e| SandboxRpcError::ViewResultError(e.to_string()))
+    }
+
+    /// Base58 encoded hash of the genesis block, taken from the `status` RPC method.
+    ///
+    /// It covers the genesis config and the initial state, so two sandboxes with the same hash started
+    /// from the same genesis. Useful to rule out config drift between runs, e.g. with [`crate::SandboxConfig::deterministic`].
+    pub async fn genesis_hash(&self) -> Result<String, SandboxRpcError> {
+        self.status()
+            .await?
+            .get("genesis_hash")
+            .and_then(|hash| hash.as_str())
+            .map(ToOwned::to_owned)
+            .ok_or(SandboxRpcError::UnexpectedResponse)
+    }
+
+    /// Genesis time of the sandbox as RFC 3339 timestamp, e.g. `2024-01-01T00:00:00.000000000Z`.
+    ///
+    /// Can be overridden with [`crate::SandboxConfig::genesis_time`].
+    pub async fn genesis_time(&self) -> Result<String, SandboxRpcError> {
+        self.rpc_call("EXPERIMENTAL_genesis_config", serde_json::Value::Null)
+            .await?
+            .get("genesis_time")
+            .and_then(|time| time.as_str())
+            .map(ToOwned::to_owned)
+            .ok_or(SandboxRpcError::UnexpectedResponse)
+    }
+
+    /// Protocol parameters the sandbox is running with, as of the final block.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use near_sandbox::*;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let sandbox = Sandbox::start_sandbox().await?;
+    /// let config = sandbox.protocol_config().await?;
+    /// println!("epoch length {}, min gas price {}", config.epoch_length, config.min_gas_price);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn protocol_config(&self) -> Result<ProtocolConfig, SandboxRpcError> {
+        let config = self
+            .rpc_call(
+                "EXPERIMENTAL_protocol_config",
+                BlockReference::Final.to_params(),
+            )
+            .await?;
+
+        ProtocolConfig::from_json(&config).ok_or(SandboxRpcError::UnexpectedResponse)
+    }
+
+    /// Balance locked per byte of the account storage, taken from [`Self::protocol_config`].
+    pub async fn storage_price_per_byte(&self) -> Result<NearToken, SandboxRpcError> {
+        Ok(self.protocol_config().await?.storage_amount_per_byte)
+    }
+
+    /// Balance an account has to hold to cover `bytes` of storage.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use near_sandbox::*;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let sandbox = Sandbox::start_sandbox().await?;
+    /// let summary = sandbox.account_summary(&"sandbox".parse()?).await?;
+    /// let locked_for_storage = sandbox.storage_cost(summary.storage_usage).await?;
+    /// println!("{locked_for_storage} is locked for {} bytes", summary.storage_usage);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn storage_cost(&self, bytes: u64) -> Result<NearToken, SandboxRpcError> {
+        Ok(self
+            .storage_price_per_byte()
+            .await?
+            .saturating_mul(u128::from(bytes)))
+    }
+
+    /// Receipts waiting to be processed as of the latest block.
+    ///
+    /// nearcore doesn't expose the number of delayed receipts, so the delayed and buffered queues
+    /// are reported as the gas attached to them, taken from the congestion info of the chunks.
+    pub async fn pending_receipts(&self) -> Result<PendingReceipts, SandboxRpcError> {
+        let block = self
+            .rpc_call("block", BlockReference::Optimistic.to_params())
+            .await?;
+        let block_height = block["header"]["height"]
+            .as_u64()
+            .ok_or(SandboxRpcError::UnexpectedResponse)?;
+
+        let mut pending = PendingReceipts {
+            block_height,
+            ..Default::default()
+        };
+        let gas = |value: &serde_json::Value| {
+            value
+                .as_str()
+                .and_then(|gas| gas.parse::<u128>().ok())
+                .unwrap_or_default()
+        };
+        for chunk in block["chunks"].as_array().into_iter().flatten() {
+            let congestion_info = &chunk["congestion_info"];
+            pending.delayed_receipts_gas += gas(&congestion_info["delayed_receipts_gas"]);
+            pending.buffered_receipts_gas += gas(&congestion_info["buffered_receipts_gas"]);
+
+            // Chunks that are missing in this block are repeated from the previous ones
+            if chunk["height_included"].as_u64() != Some(block_height) {
+                continue;
+            }
+            let chunk = self
+                .rpc_call(
+                    "chunk",
+                    serde_json::json!({ "chunk_id": chunk["chunk_hash"] }),
+                )
+                .await?;
+            pending.in_flight += chunk["receipts"]
+                .as_array()
+                .map_or(0, |receipts| receipts.len());
+        }
+
+        Ok(pending)
+    }
+
+    /// Poll [`Self::pending_receipts`] until no receipts are left to process or `timeout` elapses.
+    ///
+    /// Receipts produced in one block are only included in the next one, so the queues have to be
+    /// empty for two blocks in a row.
+    pub async fn wait_until_receipts_drained(
+        &self,
+        timeout: Duration,
+    ) -> Result<(), SandboxRpcError> {
+        let start = std::time::Instant::now();
+        let mut interval = tokio::time::interval(Duration::from_millis(100));
+        let mut drained_at: Option<u64> = None;
+
+        loop {
+            interval.tick().await;
+
+            let pending = self.pending_receipts().await?;
+            if !pending.is_drained() {
+                drained_at = None;
+            } else if drained_at.is_some_and(|height| pending.block_height > height) {
+                return Ok(());
+            } else if drained_at.is_none() {
+                drained_at = Some(pending.block_height);
+            }
+
+            if start.elapsed() > timeout {
+                return Err(SandboxRpcError::SandboxRpcError(format!(
+                    "wait_until_receipts_drained timeout: {pending:?} after {timeout:?}"
+                )));
+            }
+        }
+    }
+
+    /// Check whether the account exists in the sandbox.
+    ///
+    /// Only the "unknown account" error of `view_account` is mapped to `false`, any other error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use near_sandbox::*;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let sandbox = Sandbox::start_sandbox().await?;
+    /// assert!(!sandbox.account_exists(&"user.sandbox".parse()?).await?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn account_exists(&self, account_id: &AccountId) -> Result<bool, SandboxRpcError> {
+        match self.account_summary(account_id).await {
+            Ok(_) => Ok(true),
+            Err(e) if e.is_unknown_account() => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Poll `view_account` until the account is visible or `timeout` elapses.
+    ///
+    /// Patched state might not be visible to queries right away, so this can be used
+    /// as an explicit barrier after [`Self::create_account`] or [`Self::import_account`].
+    pub async fn wait_for_account(
+        &self,
+        account_id: &AccountId,
+        timeout: Duration,
+    ) -> Result<(), SandboxRpcError> {
+        let start = std::time::Instant::now();
+        let mut interval = tokio::time::interval(Duration::from_millis(100));
+
+        loop {
+            interval.tick().await;
+
+            match self.account_summary(account_id).await {
+                Ok(_) => return Ok(()),
+                Err(e) if start.elapsed() > timeout => {
+                    return Err(SandboxRpcError::SandboxRpcError(format!(
+                        "wait_for_account timeout: account {account_id} is not available after {timeout:?}: {e}"
+                    )));
+                }
+                Err(_) => continue,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use crate::error_kind::SandboxRpcError;
+    use crate::sandbox::tests::{
+        EMPTY_CONTRACT, JSON_CONTRACT, contract_account, sandbox_with_account,
+        sandbox_with_contract,
+    };
+    use crate::{BlockReference, Sandbox, SandboxConfig};
+
+    #[tokio::test]
+    async fn test_block_query() {
+        let sandbox = Sandbox::start_sandbox().await.unwrap();
+        sandbox.fast_forward(10).await.unwrap();
+
+        let latest = sandbox.block(BlockReference::Final).await.unwrap();
+        assert!(latest.height > 0);
+        assert!(!latest.chunks.is_empty());
+
+        let by_height = sandbox
+            .block(BlockReference::Height(latest.height))
+            .await
+            .unwrap();
+        let by_hash = sandbox
+            .block(BlockReference::Hash(latest.hash.clone()))
+            .await
+            .unwrap();
+
+        assert_eq!(latest, by_height);
+        assert_eq!(latest, by_hash);
+    }
+
+    #[tokio::test]
+    async fn test_block_timestamp() {
+        let sandbox = Sandbox::start_sandbox().await.unwrap();
+        let before = sandbox.block_timestamp().await.unwrap();
+        assert!(before > 0);
+
+        sandbox.fast_forward(10).await.unwrap();
+        assert!(sandbox.block_timestamp().await.unwrap() > before);
+    }
+
+    #[tokio::test]
+    async fn test_genesis_time_override() {
+        let config = SandboxConfig {
+            genesis_time: Some(UNIX_EPOCH + Duration::from_secs(1_704_067_200)),
+            ..Default::default()
+        };
+        let sandbox = Sandbox::start_sandbox_with_config(config).await.unwrap();
+
+        let genesis_time = sandbox.genesis_time().await.unwrap();
+        assert!(genesis_time.starts_with("2024-01-01T00:00:00"));
+    }
+
+    #[tokio::test]
+    async fn test_genesis_hash() {
+        let config = SandboxConfig {
+            deterministic: true,
+            ..Default::default()
+        };
+        let first = Sandbox::start_sandbox_with_config(config.clone())
+            .await
+            .unwrap();
+        let second = Sandbox::start_sandbox_with_config(config).await.unwrap();
+
+        let hash = first.genesis_hash().await.unwrap();
+        assert!(!hash.is_empty());
+        assert_eq!(hash, second.genesis_hash().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_receipts_drained() {
+        let sandbox = Sandbox::start_sandbox().await.unwrap();
+
+        sandbox
+            .wait_until_receipts_drained(Duration::from_secs(10))
+            .await
+            .unwrap();
+        assert!(sandbox.pending_receipts().await.unwrap().is_drained());
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_account() {
+        let sandbox = Sandbox::start_sandbox().await.unwrap();
+        let account_id: near_account_id::AccountId = "alice.sandbox".parse().unwrap();
+
+        assert!(
+            sandbox
+                .wait_for_account(&account_id, Duration::from_millis(300))
+                .await
+                .is_err()
+        );
+
+        sandbox
+            .create_account(account_id.clone())
+            .send()
+            .await
+            .unwrap();
+        sandbox
+            .wait_for_account(&account_id, Duration::from_secs(5))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_node_version() {
+        let sandbox = Sandbox::start_sandbox().await.unwrap();
+
+        let node = sandbox.node_version().await.unwrap();
+        assert!(!node.version.is_empty());
+        assert!(!node.build.is_empty());
+        assert!(node.protocol_version > 0);
+    }
+
+    #[tokio::test]
+    async fn test_protocol_config() {
+        let sandbox = Sandbox::start_sandbox().await.unwrap();
+
+        let config = sandbox.protocol_config().await.unwrap();
+        let node = sandbox.node_version().await.unwrap();
+        assert_eq!(config.protocol_version, node.protocol_version);
+        assert!(config.epoch_length > 0);
+        assert!(config.min_gas_price <= config.max_gas_price);
+        assert!(config.transaction_costs.is_object());
+    }
+
+    #[tokio::test]
+    async fn test_storage_cost() {
+        let sandbox = Sandbox::start_sandbox().await.unwrap();
+
+        let price = sandbox.storage_price_per_byte().await.unwrap();
+        assert!(!price.is_zero());
+        assert_eq!(
+            sandbox.storage_cost(100).await.unwrap(),
+            price.saturating_mul(100)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_account_balance() {
+        let sandbox = Sandbox::start_sandbox().await.unwrap();
+        let account_id: near_account_id::AccountId = "dave.sandbox".parse().unwrap();
+        let balance = near_token::NearToken::from_near(42);
+
+        sandbox
+            .create_account(account_id.clone())
+            .initial_balance(balance)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(sandbox.account_balance(&account_id).await.unwrap(), balance);
+    }
+
+    #[tokio::test]
+    async fn test_query() {
+        let account = crate::GenesisAccount {
+            account_id: "contract.sandbox".parse().unwrap(),
+            ..Default::default()
+        }
+        .with_storage_entry(b"a1".to_vec(), b"1".to_vec())
+        .with_storage_entry(b"b1".to_vec(), b"2".to_vec());
+        let sandbox = Sandbox::start_sandbox_with_config(SandboxConfig {
+            additional_accounts: vec![account.clone()],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        let state = sandbox
+            .query(super::QueryRequest::ViewState {
+                account_id: account.account_id.clone(),
+                prefix: b"a".to_vec(),
+                include_proof: false,
+            })
+            .await
+            .unwrap();
+        assert_eq!(state["values"].as_array().unwrap().len(), 1);
+
+        let keys = sandbox
+            .query(super::QueryRequest::ViewAccessKeyList {
+                account_id: account.account_id,
+            })
+            .await
+            .unwrap();
+        assert_eq!(keys["keys"].as_array().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_contract_code() {
+        let (sandbox, account) = sandbox_with_contract("contract.sandbox", []).await;
+
+        assert_eq!(
+            sandbox.contract_code(&account.account_id).await.unwrap(),
+            EMPTY_CONTRACT
+        );
+        // Accounts without a contract have no code to view
+        let no_code = "sandbox".parse().unwrap();
+        assert!(sandbox.contract_code(&no_code).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_view_call_json() {
+        let account = contract_account("contract.sandbox", []).with_code(JSON_CONTRACT.to_vec());
+        let sandbox = sandbox_with_account(&account).await;
+
+        #[derive(serde::Deserialize)]
+        struct User {
+            name: String,
+            id: u32,
+        }
+        let user: User = sandbox
+            .view_call_json(&account.account_id, "get", serde_json::json!({}))
+            .await
+            .unwrap();
+        assert_eq!(user.name, "sandbox");
+        assert_eq!(user.id, 7);
+
+        // JSON object keys have to be strings
+        let args = std::collections::HashMap::from([((1, 2), 3)]);
+        let result = sandbox
+            .view_call_json::<User>(&account.account_id, "get", args)
+            .await;
+        assert!(matches!(
+            result,
+            Err(SandboxRpcError::ArgsSerializeError(_))
+        ));
+        // The returned JSON doesn't match the expected type
+        let result = sandbox
+            .view_call_json::<Vec<u32>>(&account.account_id, "get", serde_json::json!({}))
+            .await;
+        assert!(matches!(result, Err(SandboxRpcError::ViewResultError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_access_key() {
+        let sandbox = Sandbox::start_sandbox().await.unwrap();
+        let account_id = crate::config::DEFAULT_GENESIS_ACCOUNT.to_owned();
+
+        let key = sandbox
+            .access_key(
+                &account_id,
+                crate::config::DEFAULT_GENESIS_ACCOUNT_PUBLIC_KEY,
+            )
+            .await
+            .unwrap();
+        assert_eq!(key.permission, super::AccessKeyPermission::FullAccess);
+        assert!(key.block_height > 0);
+        assert_eq!(
+            sandbox
+                .next_nonce(
+                    &account_id,
+                    crate::config::DEFAULT_GENESIS_ACCOUNT_PUBLIC_KEY,
+                )
+                .await
+                .unwrap(),
+            key.nonce + 1
+        );
+
+        let unknown_key = "ed25519:6E8sCci9badyRkXb3JoRpBj5p8C6Tw41ELDZoiihKEtp";
+        assert!(sandbox.access_key(&account_id, unknown_key).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_account_exists() {
+        let sandbox = Sandbox::start_sandbox().await.unwrap();
+        let account_id: near_account_id::AccountId = "bob.sandbox".parse().unwrap();
+
+        assert!(!sandbox.account_exists(&account_id).await.unwrap());
+        sandbox
+            .create_account(account_id.clone())
+            .send()
+            .await
+            .unwrap();
+        assert!(sandbox.account_exists(&account_id).await.unwrap());
+    }
+}
@@ -0,0 +1,238 @@
+//! Read-only view of the sandbox, for sharing a pre-seeded sandbox between tests that must not change its state.
+
+use std::time::Duration;
+
+use near_account_id::AccountId;
+use near_token::NearToken;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use crate::ConnectedSandbox;
+use crate::error_kind::SandboxRpcError;
+use crate::sandbox::patch::StateRecord;
+use crate::sandbox::query::{
+    AccessKeyView, AccountSummary, Block, BlockReference, NodeVersion, PendingReceipts,
+    ProtocolConfig, QueryRequest, SyncInfo,
+};
+use crate::sandbox::transaction::ReceiptTree;
+
+/// Restricted view of the sandbox that only exposes the queries.
+///
+/// Methods changing the state, like [`ConnectedSandbox::patch_state`], [`ConnectedSandbox::fast_forward`]
+/// or [`ConnectedSandbox::create_account`], are not available, so a test holding only a `ReadOnlySandbox`
+/// can't mutate the shared state. Note that nothing stops other holders of the sandbox from changing it.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use near_sandbox::*;
+/// use near_sandbox::sandbox::read_only::ReadOnlySandbox;
+///
+/// async fn check_seeded_state(sandbox: ReadOnlySandbox<'_>) -> Result<(), Box<dyn std::error::Error>> {
+///     assert!(sandbox.account_exists(&"alice.sandbox".parse()?).await?);
+///     Ok(())
+/// }
+///
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let sandbox = Sandbox::start_sandbox().await?;
+/// sandbox.create_account("alice.sandbox".parse()?).send().await?;
+/// check_seeded_state(sandbox.read_only()).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct ReadOnlySandbox<'a> {
+    sandbox: &'a ConnectedSandbox,
+}
+
+impl ConnectedSandbox {
+    /// Read-only view of the sandbox, see [`ReadOnlySandbox`]
+    pub const fn read_only(&self) -> ReadOnlySandbox<'_> {
+        ReadOnlySandbox { sandbox: self }
+    }
+}
+
+impl ReadOnlySandbox<'_> {
+    /// See [`ConnectedSandbox::rpc_addr`]
+    pub fn rpc_addr(&self) -> &str {
+        &self.sandbox.rpc_addr
+    }
+
+    /// See [`ConnectedSandbox::status`]
+    pub async fn status(&self) -> Result<serde_json::Value, SandboxRpcError> {
+        self.sandbox.status().await
+    }
+
+    /// See [`ConnectedSandbox::sync_info`]
+    pub async fn sync_info(&self) -> Result<SyncInfo, SandboxRpcError> {
+        self.sandbox.sync_info().await
+    }
+
+    /// See [`ConnectedSandbox::block`]
+    pub async fn block(&self, reference: BlockReference) -> Result<Block, SandboxRpcError> {
+        self.sandbox.block(reference).await
+    }
+
+    /// See [`ConnectedSandbox::block_timestamp`]
+    pub async fn block_timestamp(&self) -> Result<u64, SandboxRpcError> {
+        self.sandbox.block_timestamp().await
+    }
+
+    /// See [`ConnectedSandbox::account_summary`]
+    pub async fn account_summary(
+        &self,
+        account_id: &AccountId,
+    ) -> Result<AccountSummary, SandboxRpcError> {
+        self.sandbox.account_summary(account_id).await
+    }
+
+    /// See [`ConnectedSandbox::query`]
+    pub async fn query(&self, request: QueryRequest) -> Result<serde_json::Value, SandboxRpcError> {
+        self.sandbox.query(request).await
+    }
+
+    /// See [`ConnectedSandbox::contract_code`]
+    pub async fn contract_code(&self, account_id: &AccountId) -> Result<Vec<u8>, SandboxRpcError> {
+        self.sandbox.contract_code(account_id).await
+    }
+
+    /// See [`ConnectedSandbox::access_key`]
+    pub async fn access_key(
+        &self,
+        account_id: &AccountId,
+        public_key: &str,
+    ) -> Result<AccessKeyView, SandboxRpcError> {
+        self.sandbox.access_key(account_id, public_key).await
+    }
+
+    /// See [`ConnectedSandbox::next_nonce`]
+    pub async fn next_nonce(
+        &self,
+        account_id: &AccountId,
+        public_key: &str,
+    ) -> Result<u64, SandboxRpcError> {
+        self.sandbox.next_nonce(account_id, public_key).await
+    }
+
+    /// See [`ConnectedSandbox::account_balance`]
+    pub async fn account_balance(
+        &self,
+        account_id: &AccountId,
+    ) -> Result<NearToken, SandboxRpcError> {
+        self.sandbox.account_balance(account_id).await
+    }
+
+    /// See [`ConnectedSandbox::account_exists`]
+    pub async fn account_exists(&self, account_id: &AccountId) -> Result<bool, SandboxRpcError> {
+        self.sandbox.account_exists(account_id).await
+    }
+
+    /// See [`ConnectedSandbox::node_version`]
+    pub async fn node_version(&self) -> Result<NodeVersion, SandboxRpcError> {
+        self.sandbox.node_version().await
+    }
+
+    /// See [`ConnectedSandbox::genesis_hash`]
+    pub async fn genesis_hash(&self) -> Result<String, SandboxRpcError> {
+        self.sandbox.genesis_hash().await
+    }
+
+    /// See [`ConnectedSandbox::genesis_time`]
+    pub async fn genesis_time(&self) -> Result<String, SandboxRpcError> {
+        self.sandbox.genesis_time().await
+    }
+
+    /// See [`ConnectedSandbox::pending_receipts`]
+    pub async fn pending_receipts(&self) -> Result<PendingReceipts, SandboxRpcError> {
+        self.sandbox.pending_receipts().await
+    }
+
+    /// See [`ConnectedSandbox::protocol_config`]
+    pub async fn protocol_config(&self) -> Result<ProtocolConfig, SandboxRpcError> {
+        self.sandbox.protocol_config().await
+    }
+
+    /// See [`ConnectedSandbox::storage_price_per_byte`]
+    pub async fn storage_price_per_byte(&self) -> Result<NearToken, SandboxRpcError> {
+        self.sandbox.storage_price_per_byte().await
+    }
+
+    /// See [`ConnectedSandbox::storage_cost`]
+    pub async fn storage_cost(&self, bytes: u64) -> Result<NearToken, SandboxRpcError> {
+        self.sandbox.storage_cost(bytes).await
+    }
+
+    /// See [`ConnectedSandbox::view_call`]
+    pub async fn view_call(
+        &self,
+        contract_id: &AccountId,
+        method: &str,
+        args: &[u8],
+    ) -> Result<Vec<u8>, SandboxRpcError> {
+        self.sandbox.view_call(contract_id, method, args).await
+    }
+
+    /// See [`ConnectedSandbox::view_call_json`]
+    pub async fn view_call_json<T: DeserializeOwned>(
+        &self,
+        contract_id: &AccountId,
+        method: &str,
+        args: impl Serialize,
+    ) -> Result<T, SandboxRpcError> {
+        self.sandbox.view_call_json(contract_id, method, args).await
+    }
+
+    /// See [`ConnectedSandbox::export_state`]
+    pub async fn export_state(
+        &self,
+        accounts: &[AccountId],
+    ) -> Result<Vec<StateRecord>, SandboxRpcError> {
+        self.sandbox.export_state(accounts).await
+    }
+
+    /// See [`ConnectedSandbox::tx_receipts`]
+    pub async fn tx_receipts(
+        &self,
+        tx_hash: &str,
+        signer_id: &AccountId,
+    ) -> Result<ReceiptTree, SandboxRpcError> {
+        self.sandbox.tx_receipts(tx_hash, signer_id).await
+    }
+
+    /// See [`ConnectedSandbox::wait_for_account`]
+    pub async fn wait_for_account(
+        &self,
+        account_id: &AccountId,
+        timeout: Duration,
+    ) -> Result<(), SandboxRpcError> {
+        self.sandbox.wait_for_account(account_id, timeout).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{BlockReference, Sandbox};
+
+    #[tokio::test]
+    async fn test_read_only() {
+        let sandbox = Sandbox::start_sandbox().await.unwrap();
+        let account_id: near_account_id::AccountId = "carol.sandbox".parse().unwrap();
+        sandbox
+            .create_account(account_id.clone())
+            .send()
+            .await
+            .unwrap();
+
+        let read_only = sandbox.read_only();
+        assert_eq!(read_only.rpc_addr(), sandbox.rpc_addr);
+        assert!(read_only.account_exists(&account_id).await.unwrap());
+        assert!(
+            read_only
+                .block(BlockReference::Optimistic)
+                .await
+                .unwrap()
+                .height
+                > 0
+        );
+    }
+}
@@ -0,0 +1,221 @@
+//! Minimal signing of ed25519 transactions, for the helpers sending real transactions like [`ConnectedSandbox::fund`].
+//!
+//! Only the actions used by the crate are encoded, more complex transactions should be built with `near-api`.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
+use ed25519_dalek::{SECRET_KEY_LENGTH, Signer, SigningKey};
+use near_account_id::AccountId;
+use sha2::{Digest, Sha256};
+
+use crate::ConnectedSandbox;
+use crate::error_kind::SandboxRpcError;
+use crate::sandbox::transaction::TxOutcome;
+
+pub(crate) enum Action {
+    CreateAccount,
+    DeployContract(Vec<u8>),
+    FunctionCall {
+        method: String,
+        args: Vec<u8>,
+        gas: u64,
+        deposit: u128,
+    },
+    Transfer(u128),
+    /// Full access key with the ed25519 public key
+    AddFullAccessKey([u8; 32]),
+}
+
+impl Action {
+    /// Borsh serialization of the nearcore `Action` enum
+    fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            Self::CreateAccount => buf.push(0),
+            Self::DeployContract(code) => {
+                buf.push(1);
+                encode_bytes(buf, code);
+            }
+            Self::FunctionCall {
+                method,
+                args,
+                gas,
+                deposit,
+            } => {
+                buf.push(2);
+                encode_bytes(buf, method.as_bytes());
+                encode_bytes(buf, args);
+                buf.extend(gas.to_le_bytes());
+                buf.extend(deposit.to_le_bytes());
+            }
+            Self::Transfer(deposit) => {
+                buf.push(3);
+                buf.extend(deposit.to_le_bytes());
+            }
+            Self::AddFullAccessKey(public_key) => {
+                buf.push(5);
+                buf.push(0); // ed25519
+                buf.extend(public_key);
+                buf.extend(0u64.to_le_bytes()); // nonce
+                buf.push(1); // FullAccess
+            }
+        }
+    }
+}
+
+/// Last nonce used for each access key, shared by the clones of [`ConnectedSandbox`].
+///
+/// The nonce of the access key is updated only once the transaction is executed, so the transactions
+/// sent concurrently with the same key would all read the same one. Instead, they are submitted one
+/// at a time under the lock of the key, with the nonce following the one cached here.
+#[derive(Debug, Default)]
+pub(crate) struct NonceCache(Mutex<HashMap<(AccountId, String), KeyNonce>>);
+
+/// Last nonce of the key, locked while its transaction is being submitted
+type KeyNonce = Arc<tokio::sync::Mutex<u64>>;
+
+impl NonceCache {
+    fn key(&self, account_id: &AccountId, public_key: &str) -> KeyNonce {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry((account_id.clone(), public_key.to_owned()))
+            .or_default()
+            .clone()
+    }
+}
+
+impl ConnectedSandbox {
+    /// Sign the transaction with the ed25519 `secret_key` of `signer_id` and wait until it's executed.
+    ///
+    /// A failed execution isn't an error, check it with [`TxOutcome::into_result`].
+    pub(crate) async fn sign_and_send(
+        &self,
+        signer_id: &AccountId,
+        secret_key: &str,
+        receiver_id: &AccountId,
+        actions: &[Action],
+    ) -> Result<TxOutcome, SandboxRpcError> {
+        let signing_key = parse_secret_key(secret_key)?;
+        let public_key = encode_public_key(&signing_key);
+
+        let tx_hash = {
+            let last_nonce = self.nonces.key(signer_id, &public_key);
+            let mut last_nonce = last_nonce.lock().await;
+
+            let access_key = self.access_key(signer_id, &public_key).await?;
+            let nonce = access_key.nonce.max(*last_nonce) + 1;
+            let (tx_hash, signed_transaction) = sign_transaction(
+                &signing_key,
+                signer_id,
+                nonce,
+                receiver_id,
+                &access_key.block_hash,
+                actions,
+            )?;
+
+            // NOTE: A rejected transaction leaves a gap in the nonces, which is fine as they only have to increase
+            *last_nonce = nonce;
+            // Once the transaction is in a block, the next one of the key can't be executed before it
+            self.rpc_call(
+                "send_tx",
+                serde_json::json!({
+                    "signed_tx_base64": BASE64_STANDARD.encode(signed_transaction),
+                    "wait_until": "INCLUDED",
+                }),
+            )
+            .await?;
+
+            tx_hash
+        };
+
+        self.await_tx(&tx_hash, signer_id)
+            .await
+            .and_then(TxOutcome::from_result)
+    }
+}
+
+/// Borsh serialized `SignedTransaction` and the base58 encoded hash of the transaction
+pub(crate) fn sign_transaction(
+    signing_key: &SigningKey,
+    signer_id: &AccountId,
+    nonce: u64,
+    receiver_id: &AccountId,
+    block_hash: &str,
+    actions: &[Action],
+) -> Result<(String, Vec<u8>), SandboxRpcError> {
+    let block_hash = bs58::decode(block_hash)
+        .into_vec()
+        .map_err(|_| SandboxRpcError::UnexpectedResponse)?;
+
+    // Borsh serialized `Transaction::V0`
+    let mut transaction = vec![];
+    encode_bytes(&mut transaction, signer_id.as_str().as_bytes());
+    transaction.push(0); // ed25519
+    transaction.extend(signing_key.verifying_key().to_bytes());
+    transaction.extend(nonce.to_le_bytes());
+    encode_bytes(&mut transaction, receiver_id.as_str().as_bytes());
+    transaction.extend(block_hash);
+    transaction.extend((actions.len() as u32).to_le_bytes());
+    for action in actions {
+        action.encode(&mut transaction);
+    }
+
+    let tx_hash = Sha256::digest(&transaction);
+    let signature = signing_key.sign(&tx_hash);
+    let mut signed_transaction = transaction;
+    signed_transaction.push(0); // ed25519
+    signed_transaction.extend(signature.to_bytes());
+
+    Ok((bs58::encode(tx_hash).into_string(), signed_transaction))
+}
+
+/// Borsh serialization of strings and byte vectors: u32 length followed by the bytes
+fn encode_bytes(buf: &mut Vec<u8>, bytes: &[u8]) {
+    buf.extend((bytes.len() as u32).to_le_bytes());
+    buf.extend(bytes);
+}
+
+pub(crate) fn parse_secret_key(secret_key: &str) -> Result<SigningKey, SandboxRpcError> {
+    let invalid_key = || {
+        SandboxRpcError::SandboxRpcError(
+            "secret key must be a base58 encoded ed25519 key".to_owned(),
+        )
+    };
+
+    let bytes = secret_key
+        .strip_prefix("ed25519:")
+        .and_then(|key| bs58::decode(key).into_vec().ok())
+        .ok_or_else(invalid_key)?;
+    let seed: [u8; SECRET_KEY_LENGTH] = bytes
+        .get(..SECRET_KEY_LENGTH)
+        .and_then(|seed| seed.try_into().ok())
+        .ok_or_else(invalid_key)?;
+
+    Ok(SigningKey::from_bytes(&seed))
+}
+
+pub(crate) fn encode_public_key(signing_key: &SigningKey) -> String {
+    format!(
+        "ed25519:{}",
+        bs58::encode(signing_key.verifying_key().to_bytes()).into_string()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{DEFAULT_GENESIS_ACCOUNT_PRIVATE_KEY, DEFAULT_GENESIS_ACCOUNT_PUBLIC_KEY};
+
+    #[test]
+    fn test_public_key_from_secret_key() {
+        let signing_key = parse_secret_key(DEFAULT_GENESIS_ACCOUNT_PRIVATE_KEY).unwrap();
+        assert_eq!(
+            encode_public_key(&signing_key),
+            DEFAULT_GENESIS_ACCOUNT_PUBLIC_KEY
+        );
+        assert!(parse_secret_key("secp256k1:abc").is_err());
+    }
+}
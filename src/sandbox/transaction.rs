@@ -0,0 +1,491 @@
+//! Helpers for submitting already signed transactions to the sandbox.
+//!
+//! Transactions are passed as base64 encoded borsh serialized `SignedTransaction`, the same way the RPC accepts them,
+//! so any library (e.g. `near-api`) can be used to build and sign them.
+
+use std::collections::HashMap;
+
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
+use near_account_id::AccountId;
+use serde_json::Value;
+
+use crate::ConnectedSandbox;
+use crate::error_kind::SandboxRpcError;
+
+impl ConnectedSandbox {
+    /// Submit the signed transaction without waiting for it to be executed.
+    ///
+    /// Returns the base58 encoded transaction hash that can be passed to [`Self::await_tx`]
+    /// to get the outcome later. Useful to fire a lot of transactions for throughput tests.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use near_sandbox::*;
+    ///
+    /// # async fn example(signed_txs: Vec<String>) -> Result<(), Box<dyn std::error::Error>> {
+    /// let sandbox = Sandbox::start_sandbox().await?;
+    /// let signer_id = "sandbox".parse()?;
+    ///
+    /// let mut hashes = vec![];
+    /// for signed_tx_base64 in &signed_txs {
+    ///     hashes.push(sandbox.send_tx_async(signed_tx_base64).await?);
+    /// }
+    /// for hash in hashes {
+    ///     let outcome = sandbox.await_tx(&hash, &signer_id).await?;
+    ///     println!("{}", outcome["status"]);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn send_tx_async(
+        &self,
+        signed_tx_base64: impl AsRef<str>,
+    ) -> Result<String, SandboxRpcError> {
+        self.rpc_call(
+            "broadcast_tx_async",
+            serde_json::json!([signed_tx_base64.as_ref()]),
+        )
+        .await?
+        .as_str()
+        .map(ToOwned::to_owned)
+        .ok_or(SandboxRpcError::UnexpectedResponse)
+    }
+
+    /// Submit the signed transaction and wait until it's executed, using the `broadcast_tx_commit` RPC method.
+    ///
+    /// A failed execution isn't an error, the failure is kept structured in [`TxOutcome::failure`].
+    /// Invalid transactions (e.g. with a wrong nonce or signature) are rejected by the RPC with an error.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use near_sandbox::*;
+    ///
+    /// # async fn example(signed_tx_base64: &str) -> Result<(), Box<dyn std::error::Error>> {
+    /// let sandbox = Sandbox::start_sandbox().await?;
+    /// let outcome = sandbox.send_signed_tx(signed_tx_base64).await?;
+    /// if let Some(failure) = outcome.failure() {
+    ///     println!("transaction {} failed: {failure}", outcome.transaction_hash);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn send_signed_tx(
+        &self,
+        signed_tx_base64: impl AsRef<str>,
+    ) -> Result<TxOutcome, SandboxRpcError> {
+        self.rpc_call(
+            "broadcast_tx_commit",
+            serde_json::json!([signed_tx_base64.as_ref()]),
+        )
+        .await
+        .and_then(TxOutcome::from_result)
+    }
+
+    /// Wait for the transaction submitted with [`Self::send_tx_async`] to be executed and return its outcome.
+    ///
+    /// The outcome is the `result` of the `tx` RPC method, with the `status`, `transaction_outcome`
+    /// and `receipts_outcome` fields.
+    pub async fn await_tx(
+        &self,
+        tx_hash: &str,
+        signer_id: &AccountId,
+    ) -> Result<serde_json::Value, SandboxRpcError> {
+        self.rpc_call(
+            "tx",
+            serde_json::json!({
+                "tx_hash": tx_hash,
+                "sender_account_id": signer_id,
+                "wait_until": "EXECUTED_OPTIMISTIC",
+            }),
+        )
+        .await
+    }
+
+    /// Fetch the outcomes of all the receipts produced by the transaction, arranged by which receipt produced which.
+    ///
+    /// The root of the tree is the receipt the transaction was converted to, its children are
+    /// e.g. the cross-contract calls and refunds it produced. Waits for the transaction to be executed.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use near_sandbox::*;
+    ///
+    /// # async fn example(tx_hash: &str) -> Result<(), Box<dyn std::error::Error>> {
+    /// let sandbox = Sandbox::start_sandbox().await?;
+    /// let tree = sandbox.tx_receipts(tx_hash, &"sandbox".parse()?).await?;
+    /// for receipt in tree.iter() {
+    ///     println!("{} -> {}: {:?}", receipt.predecessor_id, receipt.executor_id, receipt.method_names);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn tx_receipts(
+        &self,
+        tx_hash: &str,
+        signer_id: &AccountId,
+    ) -> Result<ReceiptTree, SandboxRpcError> {
+        let result = self
+            .rpc_call(
+                "EXPERIMENTAL_tx_status",
+                serde_json::json!({
+                    "tx_hash": tx_hash,
+                    "sender_account_id": signer_id,
+                    "wait_until": "EXECUTED_OPTIMISTIC",
+                }),
+            )
+            .await?;
+
+        let receipts: HashMap<&str, &Value> = result["receipts"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|receipt| Some((receipt["receipt_id"].as_str()?, receipt)))
+            .collect();
+        let outcomes: HashMap<&str, &Value> = result["receipts_outcome"]
+            .as_array()
+            .ok_or(SandboxRpcError::UnexpectedResponse)?
+            .iter()
+            .filter_map(|outcome| Some((outcome["id"].as_str()?, &outcome["outcome"])))
+            .collect();
+
+        // NOTE: A transaction is always converted to a single receipt
+        let root_id = result["transaction_outcome"]["outcome"]["receipt_ids"][0]
+            .as_str()
+            .ok_or(SandboxRpcError::UnexpectedResponse)?;
+        let signer_id = result["transaction"]["signer_id"]
+            .as_str()
+            .ok_or(SandboxRpcError::UnexpectedResponse)?;
+
+        ReceiptTree::build(root_id, signer_id, &receipts, &outcomes)
+    }
+}
+
+/// Outcome of a transaction returned by [`ConnectedSandbox::send_signed_tx`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct TxOutcome {
+    /// Base58 encoded transaction hash
+    pub transaction_hash: String,
+    /// Final status of the execution, e.g. `{"SuccessValue": ""}` or `{"Failure": {...}}`
+    pub status: Value,
+    /// The whole `result` of the RPC, with the `transaction_outcome` and `receipts_outcome` fields
+    pub outcome: Value,
+}
+
+impl TxOutcome {
+    pub(crate) fn from_result(outcome: Value) -> Result<Self, SandboxRpcError> {
+        let transaction_hash = outcome["transaction_outcome"]["id"]
+            .as_str()
+            .ok_or(SandboxRpcError::UnexpectedResponse)?
+            .to_owned();
+
+        Ok(Self {
+            transaction_hash,
+            status: outcome["status"].clone(),
+            outcome,
+        })
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.failure().is_none()
+    }
+
+    /// Turn a failed execution into [`SandboxRpcError::SandboxRpcError`] with the failure
+    pub fn into_result(self) -> Result<Self, SandboxRpcError> {
+        match self.failure() {
+            Some(failure) => Err(SandboxRpcError::SandboxRpcError(failure.to_string())),
+            None => Ok(self),
+        }
+    }
+
+    /// Structured `TxExecutionError` of the failed execution, e.g. `{"ActionError": {...}}`
+    pub fn failure(&self) -> Option<&Value> {
+        self.status.get("Failure")
+    }
+
+    /// Bytes returned by the last executed method, `None` if the execution failed
+    pub fn success_value(&self) -> Option<Vec<u8>> {
+        self.status
+            .get("SuccessValue")
+            .and_then(Value::as_str)
+            .and_then(|value| BASE64_STANDARD.decode(value).ok())
+    }
+}
+
+/// Outcome of a receipt and the receipts it produced, returned by [`ConnectedSandbox::tx_receipts`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReceiptTree {
+    /// Base58 encoded receipt id
+    pub receipt_id: String,
+    /// Account that sent the receipt, `system` for refunds
+    pub predecessor_id: AccountId,
+    /// Account the receipt was executed on
+    pub executor_id: AccountId,
+    /// Names of the called methods, empty if the receipt has no function calls (e.g. a transfer or a refund)
+    pub method_names: Vec<String>,
+    pub logs: Vec<String>,
+    /// Status of the execution, e.g. `{"SuccessValue": ""}` or `{"Failure": {...}}`
+    pub status: Value,
+    pub gas_burnt: u64,
+    /// Receipts produced by this receipt
+    pub children: Vec<ReceiptTree>,
+}
+
+impl ReceiptTree {
+    fn build(
+        receipt_id: &str,
+        predecessor_id: &str,
+        receipts: &HashMap<&str, &Value>,
+        outcomes: &HashMap<&str, &Value>,
+    ) -> Result<Self, SandboxRpcError> {
+        let outcome = outcomes
+            .get(receipt_id)
+            .ok_or(SandboxRpcError::UnexpectedResponse)?;
+        let receipt = receipts.get(receipt_id).copied().unwrap_or(&Value::Null);
+
+        let executor_id: AccountId = outcome["executor_id"]
+            .as_str()
+            .and_then(|account_id| account_id.parse().ok())
+            .ok_or(SandboxRpcError::UnexpectedResponse)?;
+        // NOTE: Receipts are missing from the response only in unusual cases, so fall back to the parent executor
+        let predecessor_id: AccountId = receipt["predecessor_id"]
+            .as_str()
+            .unwrap_or(predecessor_id)
+            .parse()
+            .map_err(|_| SandboxRpcError::UnexpectedResponse)?;
+
+        let children = outcome["receipt_ids"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .map(|child_id| Self::build(child_id, executor_id.as_str(), receipts, outcomes))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            receipt_id: receipt_id.to_owned(),
+            predecessor_id,
+            executor_id,
+            method_names: receipt["receipt"]["Action"]["actions"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|action| action["FunctionCall"]["method_name"].as_str())
+                .map(ToOwned::to_owned)
+                .collect(),
+            logs: serde_json::from_value(outcome["logs"].clone()).unwrap_or_default(),
+            status: outcome["status"].clone(),
+            gas_burnt: outcome["gas_burnt"].as_u64().unwrap_or_default(),
+            children,
+        })
+    }
+
+    /// Whether the receipt was executed successfully, regardless of its children
+    pub fn is_success(&self) -> bool {
+        self.status.get("Failure").is_none()
+    }
+
+    /// Iterate over this receipt and all its descendants, depth-first in the order they were produced
+    pub fn iter(&self) -> impl Iterator<Item = &ReceiptTree> {
+        let mut stack = vec![self];
+        std::iter::from_fn(move || {
+            let receipt = stack.pop()?;
+            stack.extend(receipt.children.iter().rev());
+            Some(receipt)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::Engine;
+    use base64::prelude::BASE64_STANDARD;
+    use near_account_id::AccountId;
+    use near_token::NearToken;
+
+    use super::TxOutcome;
+    use crate::Sandbox;
+    use crate::config::{
+        DEFAULT_GENESIS_ACCOUNT, DEFAULT_GENESIS_ACCOUNT_PRIVATE_KEY,
+        DEFAULT_GENESIS_ACCOUNT_PUBLIC_KEY,
+    };
+    use crate::sandbox::contract::DEFAULT_CALL_FN_GAS;
+    use crate::sandbox::signer::{Action, parse_secret_key, sign_transaction};
+
+    #[tokio::test]
+    async fn test_send_invalid_tx_async() {
+        let sandbox = Sandbox::start_sandbox().await.unwrap();
+
+        let result = sandbox.send_tx_async("bm90IGEgdHJhbnNhY3Rpb24=").await;
+        assert!(result.is_err());
+    }
+
+    /// Hash and base64 encoded transfer of 1 NEAR from the genesis account, signed with `nonce`
+    async fn signed_transfer(
+        sandbox: &Sandbox,
+        receiver_id: &AccountId,
+        nonce: u64,
+    ) -> (String, String) {
+        let signer_id = DEFAULT_GENESIS_ACCOUNT.to_owned();
+        let access_key = sandbox
+            .access_key(&signer_id, DEFAULT_GENESIS_ACCOUNT_PUBLIC_KEY)
+            .await
+            .unwrap();
+        let (tx_hash, signed_tx) = sign_transaction(
+            &parse_secret_key(DEFAULT_GENESIS_ACCOUNT_PRIVATE_KEY).unwrap(),
+            &signer_id,
+            nonce,
+            receiver_id,
+            &access_key.block_hash,
+            &[Action::Transfer(NearToken::from_near(1).as_yoctonear())],
+        )
+        .unwrap();
+        (tx_hash, BASE64_STANDARD.encode(signed_tx))
+    }
+
+    #[tokio::test]
+    async fn test_send_tx_async() {
+        const NUM_TXS: u64 = 3;
+
+        let sandbox = Sandbox::start_sandbox().await.unwrap();
+        let signer_id = DEFAULT_GENESIS_ACCOUNT.to_owned();
+        let account_id: AccountId = "alice.sandbox".parse().unwrap();
+        sandbox
+            .create_account(account_id.clone())
+            .initial_balance(NearToken::from_near(1))
+            .send()
+            .await
+            .unwrap();
+        let nonce = sandbox
+            .access_key(&signer_id, DEFAULT_GENESIS_ACCOUNT_PUBLIC_KEY)
+            .await
+            .unwrap()
+            .nonce;
+
+        let mut hashes = vec![];
+        for i in 1..=NUM_TXS {
+            let (tx_hash, signed_tx) = signed_transfer(&sandbox, &account_id, nonce + i).await;
+            assert_eq!(sandbox.send_tx_async(&signed_tx).await.unwrap(), tx_hash);
+            hashes.push(tx_hash);
+        }
+        for tx_hash in &hashes {
+            let outcome = sandbox.await_tx(tx_hash, &signer_id).await.unwrap();
+            assert_eq!(outcome["transaction_outcome"]["id"], tx_hash.as_str());
+            assert_eq!(outcome["status"]["SuccessValue"], "");
+        }
+        assert_eq!(
+            sandbox.account_balance(&account_id).await.unwrap(),
+            NearToken::from_near(1 + NUM_TXS as u128)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_send_signed_tx() {
+        let sandbox = Sandbox::start_sandbox().await.unwrap();
+        let signer_id = DEFAULT_GENESIS_ACCOUNT.to_owned();
+        let account_id: AccountId = "alice.sandbox".parse().unwrap();
+        sandbox
+            .create_account(account_id.clone())
+            .initial_balance(NearToken::from_near(1))
+            .send()
+            .await
+            .unwrap();
+        let nonce = sandbox
+            .access_key(&signer_id, DEFAULT_GENESIS_ACCOUNT_PUBLIC_KEY)
+            .await
+            .unwrap()
+            .nonce;
+
+        let (tx_hash, signed_tx) = signed_transfer(&sandbox, &account_id, nonce + 1).await;
+        let outcome = sandbox.send_signed_tx(&signed_tx).await.unwrap();
+        assert_eq!(outcome.transaction_hash, tx_hash);
+        assert!(outcome.is_success());
+        assert_eq!(outcome.success_value(), Some(vec![]));
+        assert_eq!(
+            sandbox.account_balance(&account_id).await.unwrap(),
+            NearToken::from_near(2)
+        );
+
+        // Accepted, but the receiver doesn't exist and isn't created by a plain transfer
+        let missing: AccountId = "missing.sandbox".parse().unwrap();
+        let (_, signed_tx) = signed_transfer(&sandbox, &missing, nonce + 2).await;
+        let outcome = sandbox.send_signed_tx(&signed_tx).await.unwrap();
+        assert!(!outcome.is_success());
+        assert!(outcome.failure().unwrap().get("ActionError").is_some());
+        assert_eq!(outcome.success_value(), None);
+
+        // Rejected by the RPC, the nonce is already used
+        let (_, signed_tx) = signed_transfer(&sandbox, &account_id, nonce + 1).await;
+        assert!(sandbox.send_signed_tx(&signed_tx).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_send_invalid_signed_tx() {
+        let sandbox = Sandbox::start_sandbox().await.unwrap();
+
+        let result = sandbox.send_signed_tx("bm90IGEgdHJhbnNhY3Rpb24=").await;
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_tx_outcome() {
+        let outcome = TxOutcome::from_result(serde_json::json!({
+            "status": { "Failure": { "ActionError": { "index": 0 } } },
+            "transaction_outcome": { "id": "hash" },
+        }))
+        .unwrap();
+        assert_eq!(outcome.transaction_hash, "hash");
+        assert!(!outcome.is_success());
+        assert_eq!(outcome.failure().unwrap()["ActionError"]["index"], 0);
+        assert_eq!(outcome.success_value(), None);
+
+        let outcome = TxOutcome::from_result(serde_json::json!({
+            "status": { "SuccessValue": "MQ==" },
+            "transaction_outcome": { "id": "hash" },
+        }))
+        .unwrap();
+        assert!(outcome.is_success());
+        assert_eq!(outcome.success_value(), Some(b"1".to_vec()));
+    }
+
+    #[tokio::test]
+    async fn test_tx_receipts() {
+        let sandbox = Sandbox::start_sandbox().await.unwrap();
+        let signer_id = DEFAULT_GENESIS_ACCOUNT.to_owned();
+        let account_id: AccountId = "alice.sandbox".parse().unwrap();
+        sandbox
+            .create_account(account_id.clone())
+            .send()
+            .await
+            .unwrap();
+
+        // The account has no contract, so the call is accepted but fails during execution
+        let outcome = sandbox
+            .sign_and_send(
+                &signer_id,
+                DEFAULT_GENESIS_ACCOUNT_PRIVATE_KEY,
+                &account_id,
+                &[Action::FunctionCall {
+                    method: "missing_method".to_owned(),
+                    args: b"{}".to_vec(),
+                    gas: DEFAULT_CALL_FN_GAS,
+                    deposit: 0,
+                }],
+            )
+            .await
+            .unwrap();
+
+        let tree = sandbox
+            .tx_receipts(&outcome.transaction_hash, &signer_id)
+            .await
+            .unwrap();
+        assert_eq!(tree.predecessor_id, signer_id);
+        assert_eq!(tree.executor_id, account_id);
+        assert_eq!(tree.method_names, vec!["missing_method".to_owned()]);
+        assert!(!tree.is_success());
+        assert_eq!(tree.iter().next(), Some(&tree));
+    }
+}
@@ -0,0 +1,426 @@
+//! Thin adapter with the most used `near-workspaces` methods, to ease the migration of existing tests.
+//!
+//! Only the common subset is covered: [`sandbox`], [`Worker::dev_deploy`], [`Worker::dev_create_account`],
+//! [`Worker::create_tla`], [`Worker::view`] and [`Account::call`]. Everything else is available on the
+//! underlying [`Sandbox`] through `Deref`, or through `near-api` for more complex transactions.
+//!
+//! Only ed25519 keys are supported for signing.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! # async fn example(wasm: Vec<u8>) -> Result<(), Box<dyn std::error::Error>> {
+//! let worker = near_sandbox::workspaces::sandbox().await?;
+//! let contract = worker.dev_deploy(&wasm).await?;
+//! let account = worker.dev_create_account().await?;
+//!
+//! account
+//!     .call(contract.id(), "set_greeting")
+//!     .args_json(serde_json::json!({ "greeting": "hello" }))
+//!     .transact()
+//!     .await?
+//!     .into_result()?;
+//!
+//! let greeting: String = contract.view("get_greeting").await?.json()?;
+//! assert_eq!(greeting, "hello");
+//! # Ok(())
+//! # }
+//! ```
+
+use std::future::IntoFuture;
+use std::ops::Deref;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
+use near_account_id::AccountId;
+use near_token::NearToken;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::config::{DEFAULT_GENESIS_ACCOUNT, DEFAULT_GENESIS_ACCOUNT_PRIVATE_KEY};
+use crate::error_kind::{SandboxError, SandboxRpcError};
+use crate::sandbox::contract::serialize_args;
+use crate::sandbox::query::QueryRequest;
+use crate::sandbox::signer::{Action, encode_public_key, parse_secret_key};
+use crate::{ConnectedSandbox, Sandbox};
+
+pub use crate::sandbox::contract::DEFAULT_CALL_FN_GAS;
+
+/// Start a new sandbox, the same as `near_workspaces::sandbox()`
+pub async fn sandbox() -> Result<Worker, SandboxError> {
+    Ok(Worker {
+        sandbox: Sandbox::start_sandbox().await?,
+    })
+}
+
+/// Counterpart of `near_workspaces::Worker<Sandbox>`
+pub struct Worker {
+    sandbox: Sandbox,
+}
+
+impl Worker {
+    /// Genesis account that has [`DEFAULT_GENESIS_ACCOUNT_PRIVATE_KEY`] as the full access key
+    pub fn root_account(&self) -> Account {
+        Account::new(
+            DEFAULT_GENESIS_ACCOUNT.to_owned(),
+            DEFAULT_GENESIS_ACCOUNT_PRIVATE_KEY.to_owned(),
+            &self.sandbox,
+        )
+    }
+
+    /// Create a new account with a unique `dev-*` id, signed by [`DEFAULT_GENESIS_ACCOUNT_PRIVATE_KEY`]
+    pub async fn dev_create_account(&self) -> Result<Account, SandboxRpcError> {
+        static DEV_ACCOUNT_NONCE: AtomicU64 = AtomicU64::new(0);
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let nonce = DEV_ACCOUNT_NONCE.fetch_add(1, Ordering::Relaxed);
+        let account_id = format!("dev-{timestamp}-{nonce}.{DEFAULT_GENESIS_ACCOUNT}")
+            .parse()
+            .expect("dev account id is valid");
+
+        self.create_tla(account_id, DEFAULT_GENESIS_ACCOUNT_PRIVATE_KEY.to_owned())
+            .await
+    }
+
+    /// Create a new account and deploy `wasm` to it
+    pub async fn dev_deploy(&self, wasm: &[u8]) -> Result<Contract, SandboxRpcError> {
+        self.dev_create_account().await?.deploy(wasm).await
+    }
+
+    /// Create an account with the full access key derived from `secret_key`.
+    ///
+    /// Unlike `near-workspaces`, the account is patched into the state, so any id can be used.
+    pub async fn create_tla(
+        &self,
+        account_id: AccountId,
+        secret_key: String,
+    ) -> Result<Account, SandboxRpcError> {
+        let signing_key = parse_secret_key(&secret_key)?;
+        self.sandbox
+            .create_account(account_id.clone())
+            .public_key(encode_public_key(&signing_key))
+            .send()
+            .await?;
+
+        Ok(Account::new(account_id, secret_key, &self.sandbox))
+    }
+
+    /// Call a view method of the contract
+    pub fn view(&self, contract_id: &AccountId, method: &str) -> ViewFunction {
+        ViewFunction::new(&self.sandbox, contract_id, method)
+    }
+}
+
+impl Deref for Worker {
+    type Target = Sandbox;
+
+    fn deref(&self) -> &Self::Target {
+        &self.sandbox
+    }
+}
+
+/// Counterpart of `near_workspaces::Account`
+#[derive(Debug, Clone)]
+pub struct Account {
+    id: AccountId,
+    secret_key: String,
+    sandbox: ConnectedSandbox,
+}
+
+impl Account {
+    pub(crate) fn new(id: AccountId, secret_key: String, sandbox: &ConnectedSandbox) -> Self {
+        Self {
+            id,
+            secret_key,
+            sandbox: sandbox.clone(),
+        }
+    }
+
+    pub const fn id(&self) -> &AccountId {
+        &self.id
+    }
+
+    pub fn secret_key(&self) -> &str {
+        &self.secret_key
+    }
+
+    /// Call a method of the contract signed by this account
+    pub fn call(&self, contract_id: &AccountId, method: &str) -> CallTransaction<'_> {
+        CallTransaction {
+            signer: self,
+            contract_id: contract_id.clone(),
+            method: method.to_owned(),
+            args: Ok(vec![]),
+            gas: DEFAULT_CALL_FN_GAS,
+            deposit: NearToken::from_yoctonear(0),
+        }
+    }
+
+    /// Call a view method of the contract
+    pub fn view(&self, contract_id: &AccountId, method: &str) -> ViewFunction {
+        ViewFunction::new(&self.sandbox, contract_id, method)
+    }
+
+    /// Deploy `wasm` to this account
+    pub async fn deploy(&self, wasm: &[u8]) -> Result<Contract, SandboxRpcError> {
+        self.sign_and_send(&self.id, &[Action::DeployContract(wasm.to_vec())])
+            .await?
+            .into_result()?;
+
+        Ok(Contract {
+            account: self.clone(),
+        })
+    }
+
+    async fn sign_and_send(
+        &self,
+        receiver_id: &AccountId,
+        actions: &[Action],
+    ) -> Result<ExecutionFinalResult, SandboxRpcError> {
+        let outcome = self
+            .sandbox
+            .sign_and_send(&self.id, &self.secret_key, receiver_id, actions)
+            .await?;
+
+        Ok(ExecutionFinalResult {
+            outcome: outcome.outcome,
+        })
+    }
+}
+
+/// Counterpart of `near_workspaces::Contract`
+#[derive(Debug, Clone)]
+pub struct Contract {
+    account: Account,
+}
+
+impl Contract {
+    pub const fn id(&self) -> &AccountId {
+        self.account.id()
+    }
+
+    pub const fn as_account(&self) -> &Account {
+        &self.account
+    }
+
+    /// Call a method of this contract signed by the contract account itself
+    pub fn call(&self, method: &str) -> CallTransaction<'_> {
+        self.account.call(self.id(), method)
+    }
+
+    /// Call a view method of this contract
+    pub fn view(&self, method: &str) -> ViewFunction {
+        self.account.view(self.id(), method)
+    }
+}
+
+/// Function call transaction, sent with [`Self::transact`]
+pub struct CallTransaction<'a> {
+    signer: &'a Account,
+    contract_id: AccountId,
+    method: String,
+    args: Result<Vec<u8>, SandboxRpcError>,
+    gas: u64,
+    deposit: NearToken,
+}
+
+impl CallTransaction<'_> {
+    pub fn args(mut self, args: Vec<u8>) -> Self {
+        self.args = Ok(args);
+        self
+    }
+
+    /// Args failing to serialize are reported once the call is sent
+    pub fn args_json(mut self, args: impl serde::Serialize) -> Self {
+        self.args = serialize_args(args);
+        self
+    }
+
+    /// Gas to attach, defaults to [`DEFAULT_CALL_FN_GAS`]
+    pub const fn gas(mut self, gas: u64) -> Self {
+        self.gas = gas;
+        self
+    }
+
+    pub const fn deposit(mut self, deposit: NearToken) -> Self {
+        self.deposit = deposit;
+        self
+    }
+
+    /// Sign the transaction and wait until it's executed.
+    ///
+    /// A failed execution isn't an error, check it with [`ExecutionFinalResult::into_result`].
+    pub async fn transact(self) -> Result<ExecutionFinalResult, SandboxRpcError> {
+        let action = Action::FunctionCall {
+            method: self.method,
+            args: self.args?,
+            gas: self.gas,
+            deposit: self.deposit.as_yoctonear(),
+        };
+        self.signer
+            .sign_and_send(&self.contract_id, &[action])
+            .await
+    }
+}
+
+/// Call of a view method, executed once awaited
+pub struct ViewFunction {
+    sandbox: ConnectedSandbox,
+    contract_id: AccountId,
+    method: String,
+    args: Result<Vec<u8>, SandboxRpcError>,
+}
+
+impl ViewFunction {
+    fn new(sandbox: &ConnectedSandbox, contract_id: &AccountId, method: &str) -> Self {
+        Self {
+            sandbox: sandbox.clone(),
+            contract_id: contract_id.clone(),
+            method: method.to_owned(),
+            args: Ok(vec![]),
+        }
+    }
+
+    pub fn args(mut self, args: Vec<u8>) -> Self {
+        self.args = Ok(args);
+        self
+    }
+
+    /// Args failing to serialize are reported once the call is sent
+    pub fn args_json(mut self, args: impl serde::Serialize) -> Self {
+        self.args = serialize_args(args);
+        self
+    }
+}
+
+impl IntoFuture for ViewFunction {
+    type Output = Result<ViewResultDetails, SandboxRpcError>;
+    type IntoFuture = Pin<Box<dyn Future<Output = Self::Output> + Send>>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        Box::pin(async move {
+            let result = self
+                .sandbox
+                .query(QueryRequest::CallFunction {
+                    account_id: self.contract_id,
+                    method_name: self.method,
+                    args: self.args?,
+                })
+                .await?;
+
+            Ok(ViewResultDetails {
+                result: serde_json::from_value(result.get("result").cloned().unwrap_or_default())
+                    .map_err(|_| SandboxRpcError::UnexpectedResponse)?,
+                logs: serde_json::from_value(result.get("logs").cloned().unwrap_or_default())
+                    .unwrap_or_default(),
+            })
+        })
+    }
+}
+
+/// Result of a view method
+#[derive(Debug, Clone)]
+pub struct ViewResultDetails {
+    pub result: Vec<u8>,
+    pub logs: Vec<String>,
+}
+
+impl ViewResultDetails {
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T, SandboxRpcError> {
+        parse_json(&self.result)
+    }
+}
+
+/// Result of a transaction, the `result` of the `send_tx` RPC method
+#[derive(Debug, Clone)]
+pub struct ExecutionFinalResult {
+    pub outcome: Value,
+}
+
+impl ExecutionFinalResult {
+    pub fn is_success(&self) -> bool {
+        self.outcome["status"].get("SuccessValue").is_some()
+    }
+
+    pub fn is_failure(&self) -> bool {
+        self.outcome["status"].get("Failure").is_some()
+    }
+
+    /// Turn a failed execution into [`SandboxRpcError::SandboxRpcError`] with the failure
+    pub fn into_result(self) -> Result<Self, SandboxRpcError> {
+        match self.outcome["status"].get("Failure") {
+            Some(failure) => Err(SandboxRpcError::SandboxRpcError(failure.to_string())),
+            None => Ok(self),
+        }
+    }
+
+    /// Value returned by the called method
+    pub fn raw_bytes(&self) -> Result<Vec<u8>, SandboxRpcError> {
+        self.outcome["status"]
+            .get("SuccessValue")
+            .and_then(Value::as_str)
+            .and_then(|value| BASE64_STANDARD.decode(value).ok())
+            .ok_or(SandboxRpcError::UnexpectedResponse)
+    }
+
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T, SandboxRpcError> {
+        parse_json(&self.raw_bytes()?)
+    }
+}
+
+fn parse_json<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, SandboxRpcError> {
+    serde_json::from_slice(bytes)
+        .map_err(|e| SandboxRpcError::SandboxRpcError(format!("Failed to parse the result: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_call_signed_transaction() {
+        let worker = sandbox().await.unwrap();
+        let account = worker.dev_create_account().await.unwrap();
+        let root = worker.root_account();
+
+        // The account has no contract, so the call is accepted but fails during execution
+        let result = root
+            .call(account.id(), "missing_method")
+            .args_json(serde_json::json!({}))
+            .transact()
+            .await
+            .unwrap();
+        assert!(result.is_failure());
+        assert!(result.into_result().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_args_json_error() {
+        let worker = sandbox().await.unwrap();
+        let root = worker.root_account();
+        // JSON object keys have to be strings
+        let args = std::collections::HashMap::from([((1, 2), 3)]);
+
+        let result = root
+            .call(root.id(), "method")
+            .args_json(&args)
+            .transact()
+            .await;
+        assert!(matches!(
+            result,
+            Err(SandboxRpcError::ArgsSerializeError(_))
+        ));
+
+        let result = root.view(root.id(), "method").args_json(&args).await;
+        assert!(matches!(
+            result,
+            Err(SandboxRpcError::ArgsSerializeError(_))
+        ));
+    }
+}